@@ -3,14 +3,15 @@
 use std::{any::TypeId, borrow::BorrowMut, collections::HashMap, f64, fmt::{Alignment, Debug, Display, Pointer}, iter::{self, Once}, mem, ops::DerefMut, os::linux::fs::MetadataExt, str::{Chars, FromStr}};
 use std::ops::*;
 use itertools::Itertools;
-use num::Float;
+use num::{BigInt, BigRational, Float, Zero};
 
 use crate::value::*;
 use crate::ops::*;
 use crate::verb::*;
+use crate::lexer::{lex_spans, Span};
 
 use crate::{Adverb, Array, Func, Part, Conj, PrimConj, PseudoChar, Val, Verb, PrimVerb};
-use super::{ALError, Env};
+use super::{ALError, Env, Scope};
 
 
 #[derive(Debug, Clone, PartialEq)]
@@ -23,7 +24,7 @@ pub enum Token {
     Mark,
     Lpar,
     Rpar,
-    Asgn,
+    Asgn { local: bool },
 }
 
 impl Display for Token {
@@ -35,6 +36,8 @@ impl Display for Token {
             Ident(s) => f.write_str(s),
             Noun(n) => Dsp::fmt(n, f),
             Verb(v) => Dsp::fmt(v, f),
+            Adv(a) => Dsp::fmt(a, f),
+            Conj(c) => Dsp::fmt(c, f),
             y => Dbg::fmt(y, f),
         }
         
@@ -44,7 +47,7 @@ impl Display for Token {
 impl Token {
     fn edge(&self) -> bool {
         use Token::*;
-        matches!(self, Lpar | Mark | Asgn)
+        matches!(self, Lpar | Mark | Asgn { .. })
     }
 
     fn avn(&self) -> bool {
@@ -59,66 +62,178 @@ impl Token {
 }
 
 
-pub fn eval(mut words: Vec<&str>, env: &mut Env) -> Result<Option<Val>, super::ALError> {
-    let mut toks: Vec<Token> = Vec::with_capacity(words.len());
+pub fn eval(src: &str, env: &mut Env) -> Result<Option<Val>, super::ALError> {
+    let mut trace = Trace::new(env.trace);
+    let r = reduce(src, env, &mut trace);
+    print!("{trace}");
+    r
+}
+
+/// The reductions of one sentence, shown as a table when tracing is on.
+pub struct Trace {
+    on: bool,
+    /// rule name, the words it consumed and what they reduced to
+    rows: Vec<(&'static str, Vec<String>, String)>,
+}
+
+impl Trace {
+    pub fn new(on: bool) -> Self {
+        Trace { on, rows: Vec::new() }
+    }
+
+    fn step(&mut self, rule: &'static str, words: &[&dyn Display]) {
+        if self.on {
+            self.rows.push((rule, words.iter().map(|w| plain(w.to_string())).collect(), String::new()));
+        }
+    }
+
+    fn result(&mut self, r: &dyn Display) {
+        if let (true, Some(row)) = (self.on, self.rows.last_mut()) {
+            row.2 = plain(r.to_string());
+        }
+    }
+}
+
+/// Strips the colour escapes so cells can be measured.
+fn plain(s: String) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+impl Display for Trace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        // a rule column, three word columns and the result
+        let cells = self.rows.iter()
+            .map(|(rule, words, r)| {
+                let mut row = vec![rule.to_string()];
+                row.extend(words.iter().cloned());
+                row.resize(4, String::new());
+                row.push(r.clone());
+                row.into_iter().map(|c| c.lines().map(str::to_string).collect_vec()).collect_vec()
+            })
+            .collect_vec();
+        let widths = (0..5)
+            .map(|i| cells.iter()
+                .flat_map(|row| row[i].iter().map(|l| l.chars().count()))
+                .max()
+                .unwrap_or(0))
+            .collect_vec();
+        let rule = |l: char, m: char, r: char| {
+            format!("{l}{}{r}\n", widths.iter().map(|w| "─".repeat(w + 2)).join(&m.to_string()))
+        };
+
+        f.write_str(&rule('┌', '┬', '┐'))?;
+        for (n, row) in cells.iter().enumerate() {
+            if n > 0 {
+                f.write_str(&rule('├', '┼', '┤'))?;
+            }
+            let height = row.iter().map(Vec::len).max().unwrap_or(0).max(1);
+            for i in 0..height {
+                let line = row.iter().zip(&widths)
+                    .map(|(c, w)| format!(" {:w$} ", c.get(i).map_or("", |l| l.as_str())))
+                    .join("│");
+                f.write_fmt(format_args!("│{line}│\n"))?;
+            }
+        }
+        f.write_str(&rule('└', '┴', '┘'))
+    }
+}
+
+fn reduce(src: &str, env: &mut Env, trace: &mut Trace) -> Result<Option<Val>, super::ALError> {
+    let mut words = lex_spans(src);
+    if words.last().is_some_and(|(w, _)| w.starts_with("NB.")) {
+        words.pop();
+    }
+    reduce_words(src, words, env, trace)
+}
+
+/// Parses and runs the words of `src`, which may be only part of it.
+fn reduce_words(src: &str, mut words: Vec<(&str, Span)>, env: &mut Env, trace: &mut Trace) -> Result<Option<Val>, super::ALError> {
+    let mut toks: Vec<(Token, Span)> = Vec::with_capacity(words.len());
     if words.is_empty() {return Ok(None);}
+    let mark = || (Token::Mark, Span::default());
 
-    while !words.is_empty() || toks.len() >= 1 {
-        println!("toks: [{}]",  toks.iter().format(", "));
+    while !words.is_empty() || !toks.is_empty() {
         //println!("words: {words:?}");
         use Token::*;
 
         toks = match toks.as_mut_slice() {
-            [ e, Verb(v), Noun(y), rest@.. ] if e.edge() => {
-                println!("mon");
+            [ e, (Verb(v), vs), (Noun(y), ys), rest@.. ] if e.0.edge() => {
+                trace.step("0 Monad", &[v, y]);
                 let mut rest = rest.to_vec();
                 let v = mem::replace(v, crate::Verb::Prim(PrimVerb::plus));
                 let y = mem::replace(y, Val::Int(-1));
 
-                let r = eval_mon(v, y)?;
-                rest.insert(0, Noun(r));
-                rest.insert(0, mem::replace(e, Token::Mark));
+                let r = eval_mon(v, y, env).map_err(|err| err.at(src, *vs))?;
+                trace.result(&r);
+                rest.insert(0, (Noun(r), vs.to(*ys)));
+                rest.insert(0, mem::replace(e, mark()));
 
                 rest
             },
-            [ e, u@Verb(_), Verb(v), Noun(y), rest@.. ] if e.edge() || e.avn() => {
-                println!("mon");
+            [ e, u@(Verb(_), _), (Verb(v), vs), (Noun(y), ys), rest@.. ] if e.0.edge() || e.0.avn() => {
+                trace.step("1 Monad", &[v, y]);
                 let mut rest = rest.to_vec();
                 let v = mem::replace(v, crate::Verb::Prim(PrimVerb::plus));
                 let y = mem::replace(y, Val::Int(-1));
-                let r = eval_mon(v, y)?;
-                rest.insert(0, Noun(r));
-                rest.insert(0, mem::replace(u, Token::Mark));
-                rest.insert(0, mem::replace(e, Token::Mark));
+                let r = eval_mon(v, y, env).map_err(|err| err.at(src, *vs))?;
+                trace.result(&r);
+                rest.insert(0, (Noun(r), vs.to(*ys)));
+                rest.insert(0, mem::replace(u, mark()));
+                rest.insert(0, mem::replace(e, mark()));
 
                 rest
             },
-            [e, Noun(x), Verb(v), Noun(y), rest@..]if e.edge() || e.avn()  => {
-                println!("dyd");
+            [e, (Noun(x), xs), (Verb(v), vs), (Noun(y), ys), rest@..]if e.0.edge() || e.0.avn()  => {
+                trace.step("2 Dyad", &[x, v, y]);
                 let mut rest = rest.to_vec();
                 let x = mem::replace(x, Val::Int(-1));
                 let v = mem::replace(v, crate::Verb::Prim(PrimVerb::plus));
                 let y = mem::replace(y, Val::Int(-1));
-                let r = eval_dyd(v, x, y)?;
-                rest.insert(0, Noun(r));
-                rest.insert(0, mem::replace(e, Token::Mark));
+                release(&e.0, &x, &v, &y, &words, env);
+                let r = eval_dyd(v, x, y, env).map_err(|err| err.at(src, *vs))?;
+                trace.result(&r);
+                rest.insert(0, (Noun(r), xs.to(*ys)));
+                rest.insert(0, mem::replace(e, mark()));
 
 
                 rest
             },
             //[ e, u@(Verb(_) | Noun(_)), Adv(a), rest@.. ] if e.edge() || e.avn() => {
-            [ e, Verb(u) , Adv(a), rest@.. ] if e.edge() || e.avn() => {
-                println!("adv");
+            [ e, (Verb(u), us), (Adv(a), as_), rest@.. ] if e.0.edge() || e.0.avn() => {
+                trace.step("3 Adverb", &[u, a]);
                 let mut rest = rest.to_vec();
                 let a = mem::replace(a, crate::Adverb::Prim(PrimAdv::slsh));
                 
                 //rest.insert(0, Verb(crate::Verb::Adv { u: Box::new(u.clone()), src: a }));
-                rest.insert(0, Verb(a.cons(u.clone())));
-                rest.insert(0, mem::replace(e, Token::Mark));
+                let r = a.cons(u.clone()).map_err(|err| err.at(src, *as_))?;
+                trace.result(&r);
+                rest.insert(0, (Verb(r), us.to(*as_)));
+                rest.insert(0, mem::replace(e, mark()));
+                rest
+            },
+            [ e, (Noun(m), ms), (Conj(crate::Conj::Prim(PrimConj::col)), cs), (Noun(n), ns), rest@.. ] if e.0.edge() || e.0.avn() => {
+                trace.step("4 Conj", &[m, &':', n]);
+                let mut rest = rest.to_vec();
+                let def = eval_def(m.clone(), n.clone(), env).map_err(|err| err.at(src, *cs))?;
+                trace.result(&def);
+                rest.insert(0, (def, ms.to(*ns)));
+                rest.insert(0, mem::replace(e, mark()));
                 rest
             },
-            [ e, u@(Verb(_) | Noun(_)), Conj(c), v@(Verb(_) | Noun(_)), rest@.. ] if e.edge() || e.avn() => {
-                println!("conj");
+            [ e, (u@(Verb(_) | Noun(_)), us), (Conj(c), cs), (v@(Verb(_) | Noun(_)), vs), rest@.. ] if e.0.edge() || e.0.avn() => {
+                trace.step("4 Conj", &[u, c, v]);
                 let mut rest = rest.to_vec();
 
                 let u = match u {
@@ -133,58 +248,65 @@ pub fn eval(mut words: Vec<&str>, env: &mut Env) -> Result<Option<Val>, super::A
                     _ => unreachable!(),
                 };
 
-                rest.insert(0, Verb(c.clone().cons2(u, v)));
-                rest.insert(0, mem::replace(e, Token::Mark));
+                let r = c.clone().cons2(u, v).map_err(|err| err.at(src, *cs))?;
+                trace.result(&r);
+                rest.insert(0, (Verb(r), us.to(*vs)));
+                rest.insert(0, mem::replace(e, mark()));
                 rest
             },
-            [e, Verb(f), Verb(g), Verb(h), rest@..] if e.edge() || e.avn() => {
-                println!("fork");
+            [e, (Verb(f), fs), (Verb(g), _), (Verb(h), hs), rest@..] if e.0.edge() || e.0.avn() => {
+                trace.step("5 Trident", &[f, g, h]);
                 let mut rest = rest.to_vec();
-                
-                rest.insert(0, Verb(crate::Verb::Fork { 
+                let r = crate::Verb::Fork { 
                     f: Box::new(f.clone()), 
                     g: Box::new(g.clone()), 
                     h: Box::new(h.clone())
-                }));
+                };
+                trace.result(&r);
+                rest.insert(0, (Verb(r), fs.to(*hs)));
 
-                rest.insert(0, mem::replace(e, Token::Mark));
+                rest.insert(0, mem::replace(e, mark()));
                 //println!("rest: {rest:?}");
                 //println!("w: {words:?}");
                 rest
             },
-            [e, f, g, h, rest@..] if e.edge() && f.cavn() && g.cavn() && h.cavn() => {
-                println!("train");
+            [e, (f, fs), (g, _), (h, hs), rest@..] if e.0.edge() && f.cavn() && g.cavn() && h.cavn() => {
+                trace.step("5 Trident", &[f, g, h]);
                 let mut rest = rest.to_vec();
 
-                rest.insert(0, eval_train(f.clone(), g.clone(), h.clone()));
-                rest.insert(0, mem::replace(e, Token::Mark));
+                let r = eval_train(f.clone(), g.clone(), h.clone()).map_err(|err| err.at(src, fs.to(*hs)))?;
+                trace.result(&r);
+                rest.insert(0, (r, fs.to(*hs)));
+                rest.insert(0, mem::replace(e, mark()));
                 rest
 
             },
 
-            [Ident(s), Asgn, y, rest@..] if y.cavn()  => {
-                println!("asgn");
+            [(Ident(s), _), (Asgn { local }, _), y, rest@..] if y.0.cavn()  => {
+                trace.step("7 Is", &[s, &if *local { "=." } else { "=:" }, &y.0]);
+                trace.result(&y.0);
                 let mut rest = rest.to_vec();
-                let y = mem::replace(y, Token::Mark);
+                let (y, ys) = mem::replace(y, mark());
                 // TODO: borrow env
-                rest.insert(0, y.clone()); 
+                rest.insert(0, (y.clone(), ys)); 
                 let y = match y {
                      Conj(y) => Val::ValFunc(Func::C(y)),
                      Verb(y) => Val::ValFunc(Func::V(y)),
                      Adv(y) => Val::ValFunc(Func::A(y)),
                      Noun(y) => y,
-                    y => panic!("not cavn: {y:?}"),
+                    y => return Err(ALError::Syntax(format!("cannot assign {y}")).at(src, ys)),
                 };
-                let s = mem::replace(s, String::new());
+                let s = std::mem::take(s);
                 
-                env.names.insert( s.clone(), y);
+                env.assign(s, y, *local);
                 rest
             },
-            [Lpar, v, Rpar, any@..] => {
-                println!("punc");
+            [(Lpar, ls), (v, _), (Rpar, rs), any@..] => {
+                trace.step("8 Paren", &[&'(', v, &')']);
+                trace.result(v);
                 let mut restv = any.to_vec();
                 //restv.rotate_left(mid)
-                restv.insert(0, mem::replace(v, Token::Mark));
+                restv.insert(0, (mem::replace(v, Token::Mark), ls.to(*rs)));
                 restv
             },
             //m => unreachable!("bad match: {m:?}"),
@@ -192,42 +314,123 @@ pub fn eval(mut words: Vec<&str>, env: &mut Env) -> Result<Option<Val>, super::A
             //toks.splice(lo..hi, iter::once(y));
             //restv
             //},
-            [y] | [Mark, y] if words.is_empty() => {
+            [(y, ys)] | [(Mark, _), (y, ys)] if words.is_empty() => {
                 return match y {
                     Noun(y) => Ok(Some(y.clone())),
-                    y => Err(ALError::Value(format!("{y:?}")))
+                    Verb(y) => Ok(Some(Val::ValFunc(Func::V(y.clone())))),
+                    Adv(y) => Ok(Some(Val::ValFunc(Func::A(y.clone())))),
+                    Conj(y) => Ok(Some(Val::ValFunc(Func::C(y.clone())))),
+                    y => Err(ALError::Value(format!("{y:?}")).at(src, *ys))
                 };
             },
             rest if !words.is_empty() => {
-                println!("move");
                 let mut t = rest.to_vec();
-                if let Some(Asgn) = rest.first() {
-                    t.insert(0, move_words(&mut words, env, true)?);
-                } else {
-                    t.insert(0, move_words(&mut words, env, false)?);
-                }
+                let asgn = matches!(rest.first(), Some((Asgn { .. }, _)));
+                let w = match if asgn { None } else { list(src, &mut words, env, trace)? } {
+                    Some(w) => w,
+                    None => move_words(&mut words, env, asgn).map_err(|(err, span)| err.at(src, span))?,
+                };
+                t.insert(0, w);
                 t
             },
-            [m, rest@..] if words.is_empty() && *m != Token::Mark => {
-                println!("mark");
+            [m, rest@..] if words.is_empty() && m.0 != Token::Mark => {
                 let mut rest = rest.to_vec();
-                rest.insert(0, mem::replace(m, Token::Mark));
-                rest.insert(0, Token::Mark);
+                rest.insert(0, mem::replace(m, mark()));
+                rest.insert(0, mark());
                 rest
 
             },
             _ => {
-                println!("unexptected state: {toks:?} {words:?}") ;
-                return  Err(ALError::Syntax);
+                let span = toks.iter()
+                    .map(|(_, s)| *s)
+                    .filter(|s| *s != Span::default())
+                    .reduce(Span::to)
+                    .unwrap_or_default();
+                return  Err(ALError::Syntax("unexpected sequence of words".to_string()).at(src, span));
             },
         };
     }
 
-    println!("end toks: [{}]",  toks.iter().format(", "));
-    println!("end words: {words:?}");
     Ok(None)
 }
 
+/// The index of the first word of the list, strand item or parenthesis ending at word `end`,
+/// `None` if its opening bracket is missing.
+fn item_start(words: &[(&str, Span)], end: usize) -> Option<usize> {
+    let mut depth = 0;
+    for i in (0..=end).rev() {
+        match words[i].0 {
+            ")" | "⟩" => depth += 1,
+            "(" | "⟨" => depth -= 1,
+            _ => {},
+        }
+        if depth <= 0 {
+            return (depth == 0).then_some(i);
+        }
+    }
+    None
+}
+
+/// Reads the list `⟨a, b, c⟩` or the strand `a‿b‿c` that ends `words`, running each element
+/// as a sentence of its own. Either makes a list of boxes.
+fn list(src: &str, words: &mut Vec<(&str, Span)>, env: &mut Env, trace: &mut Trace) -> Result<Option<(Token, Span)>, ALError> {
+    let end = words.len() - 1;
+    let unmatched = |msg: &str| Err(ALError::Syntax(msg.to_string()).at(src, words[end].1));
+    match words[end].0 {
+        "⟨" => return unmatched("unmatched ⟨"),
+        "‿" => return unmatched("strand without a right item"),
+        _ => {},
+    }
+    let Some(mut first) = item_start(words, end) else {
+        return if words[end].0 == "⟩" { unmatched("unmatched ⟩") } else { Ok(None) };
+    };
+    let strand = first > 0 && words[first - 1].0 == "‿";
+    if !strand && words[end].0 != "⟩" {
+        return Ok(None);
+    }
+    let span = words[end].1;
+    let mut items = Vec::new();
+    if strand {
+        items.push(first..end + 1);
+        while first > 0 && words[first - 1].0 == "‿" {
+            let start = first.checked_sub(2).and_then(|e| item_start(words, e));
+            let Some(start) = start.filter(|&s| words[s].0 != "‿") else {
+                return Err(ALError::Syntax("strand without a left item".to_string()).at(src, words[first - 1].1));
+            };
+            items.push(start..first - 1);
+            first = start;
+        }
+        items.reverse();
+    } else {
+        // split the inside of the brackets at the separators outside any nesting
+        let mut depth = 0;
+        let mut start = first + 1;
+        for (i, (w, _)) in words.iter().enumerate().take(end).skip(first + 1) {
+            match *w {
+                "(" | "⟨" => depth += 1,
+                ")" | "⟩" => depth -= 1,
+                "," | "⋄" if depth == 0 => {
+                    items.push(start..i);
+                    start = i + 1;
+                },
+                _ => {},
+            }
+        }
+        if start < end || !items.is_empty() {
+            items.push(start..end);
+        }
+    }
+    let span = words[first].1.to(span);
+    let data = items.into_iter()
+        .map(|r| match reduce_words(src, words[r.clone()].to_vec(), env, trace)? {
+            Some(y) => Ok(y),
+            None => Err(ALError::Syntax("empty list element".to_string()).at(src, words[r.start.min(end)].1)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    words.truncate(first);
+    Ok(Some((Token::Noun(Array::from(data).into()), span)))
+}
+
 /*
 EDGE,      VERB,      NOUN, ANY,       monad,   ..., 1,2, ...,
 EDGE+AVN,  VERB,      VERB, NOUN,      monad,   ..., 2,3, ...,
@@ -243,57 +446,77 @@ LPAR,      CAVN,      RPAR, ANY,       punc,    ..., 0,2, ...,
 
 //fn eval_match(a: &mut Token, b: &mut Token, c: &mut Token, d: &mut Token) -> (usize, usize) { }
 
-fn move_words(words: &mut Vec<&str>, env: &mut Env, asgn: bool) -> Result<Token, ALError> {
-    let w = words.pop().unwrap();
+/// Drops the value of the name `asgn` is about to rebind when `x v y` is sure to give the new one,
+/// leaving an argument that shares its atoms their only owner, so `a =: a + 1` writes over them.
+fn release(asgn: &Token, x: &Val, v: &Verb, y: &Val, words: &[(&str, Span)], env: &mut Env) {
+    if let (Token::Asgn { local }, Some((name, _))) = (asgn, words.last()) {
+        if total(v, x, y) {
+            env.take(name, *local);
+        }
+    }
+}
+
+fn move_words(words: &mut Vec<(&str, Span)>, env: &mut Env, asgn: bool) -> Result<(Token, Span), (ALError, Span)> {
+    let (w, span) = words.pop().unwrap();
     let wb = w.as_bytes();
 
     if let Ok(pc) = PseudoChar::try_from(w) {
-         Ok(match pc.part {
+         Ok((match pc.part {
             Part::Verb(v) => Token::Verb(Verb::Prim(v)),
             Part::Adv(a) => Token::Adv(Adverb::Prim(a)),
             Part::Conj(c) => Token::Conj(Conj::Prim(c)),
             Part::Lpar => Token::Lpar,
             Part::Rpar => Token::Rpar,
-            Part::Asgn => Token::Asgn,
-            _ => unreachable!("unhanded char: {pc:?}"),
-        })
+            Part::Asgn { local } => Token::Asgn { local },
+            Part::Null => return Err((ALError::Syntax(format!("unknown word {w}")), span)),
+        }, span))
     } else if asgn {
-        Ok(Token::Ident(match wb[0] {
-            b'a'..=b'z' | b'A'..=b'Z' =>  String::from_utf8(wb.to_vec()).expect("not utf8"),
-            b'\'' => String::from_utf8(wb[1..wb.len()-1].to_vec()).expect("not utf8"),
-            b'_' | b'0'..=b'9' => panic!("not a name"),
-            _ => panic!("unhandled: {w:?}")
-        }))
+        Ok((Token::Ident(match wb[0] {
+            b'a'..=b'z' | b'A'..=b'Z' =>  w.to_string(),
+            b'\'' if w.len() > 2 => w[1..w.len()-1].to_string(),
+            _ => return Err((ALError::Syntax(format!("cannot assign to {w}")), span)),
+        }), span))
     } else {
         match wb[0] {
             b'a'..=b'z' | b'A'..=b'Z' =>  {
-                let s = String::from_utf8(wb.to_vec()).expect("not utf8");
-                if let Some(y) = env.names.get(&s) {
-                    Ok(match y.clone() {
+                let s = w.to_string();
+                if let Some(y) = env.get(&s) {
+                    Ok((match y {
                         Val::ValFunc(y) => match y {
                             Func::A(y) => Token::Adv(y),
                             Func::C(y) => Token::Conj(y),
                             Func::V(y) => Token::Verb(y),
                         }
                         y  => Token::Noun(y), 
-                    } )
+                    }, span))
                 } else {
-                    Err(ALError::Value(s))
+                    Err((ALError::Value(s), span))
                 }
 
             },
-            b'\'' => Ok(Token::Noun({
+            b'\'' if w.len() < 2 || !w.ends_with('\'') => Err((ALError::Syntax("unmatched quote".to_string()), span)),
+            b'\'' => Ok((Token::Noun({
                 //wb[1..wb.len()-1].into_iter().map(|c| *c as char).collect_vec()
-                parse_escapes(
+                let text = parse_escapes(
                     w.chars()
                         .dropping(1)
                         .dropping_back(1))
-                    .unwrap()
-                    .into()
-            })),
-            b'`' => Ok(Token::Noun(Val::Sym(env.syms.get_or_intern(String::from_utf8(wb[1..].to_vec()).expect("not utf8"))))),
-            b'_' | b'0'..=b'9' => Ok(Token::Noun(parse_nums(w, words))),
-            _ => panic!("unhandled: {w:?}")
+                    .map_err(|e| (ALError::Syntax(e.to_string()), span))?;
+                // as in J, a single character is an atom
+                match text[..] {
+                    [c] => c.into(),
+                    _ => text.into(),
+                }
+            }), span)),
+            b'`' => {
+                let (y, span) = parse_syms((w, span), words);
+                Ok((Token::Noun(y), span))
+            },
+            b'_' | b'0'..=b'9' => {
+                let (y, span) = parse_nums((w, span), words).map_err(|e| (e, span))?;
+                Ok((Token::Noun(y), span))
+            },
+            _ => Err((ALError::Syntax(format!("unknown word {w}")), span)),
         }
     }
 }
@@ -301,14 +524,27 @@ fn move_words(words: &mut Vec<&str>, env: &mut Env, asgn: bool) -> Result<Token,
 #[derive(Debug, Clone)]
 enum ParseEscapeError {
     UnexpectedEscape(char),
-    NYI,
+    Nyi(char),
+}
+
+impl Display for ParseEscapeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseEscapeError::UnexpectedEscape(c) => write!(f, "unexpected escape \\{c}"),
+            ParseEscapeError::Nyi(c) => write!(f, "escape \\{c} not yet supported"),
+        }
+    }
 }
 
 // todo trait method on iterator/chars
-fn parse_escapes<I: Iterator<Item = char>>(mut iter: I) -> Result<Vec<char>, ParseEscapeError> {
+fn parse_escapes<I: Iterator<Item = char>>(iter: I) -> Result<Vec<char>, ParseEscapeError> {
+    let mut iter = iter.peekable();
     let mut res = Vec::new();
     while let Some(c) = iter.next() {
-        if c == '\\' {
+        if c == '\'' && iter.peek() == Some(&'\'') {
+            iter.next();
+            res.push(c);
+        } else if c == '\\' {
             match iter.next()  {
                 Some('0') => res.push('\0'),
                 Some('\'') => res.push('\''),
@@ -316,7 +552,7 @@ fn parse_escapes<I: Iterator<Item = char>>(mut iter: I) -> Result<Vec<char>, Par
                 Some('r') => res.push('\r'),
                 Some('t') => res.push('\t'),
                 Some('n') => res.push('\n'),
-                Some('x' | 'u') => return Err(ParseEscapeError::NYI),
+                Some(c@('x' | 'u')) => return Err(ParseEscapeError::Nyi(c)),
                 Some(c)  => return Err(ParseEscapeError::UnexpectedEscape(c)),
                 None => break,
             }
@@ -328,101 +564,286 @@ fn parse_escapes<I: Iterator<Item = char>>(mut iter: I) -> Result<Vec<char>, Par
 }
 
 
-fn parse_nums(w: &str, words: &mut Vec<&str>) -> Val {
+/// Spells a J number the way Rust parses it: `_` is the minus sign, `_` and `__` alone are infinities.
+fn num_text(w: &str) -> String {
+    match w {
+        "_" => "inf".to_string(),
+        "__" => "-inf".to_string(),
+        w => w.replace('_', "-"),
+    }
+}
+
+/// Parses an extended number, an integer ending in `x` or a rational `NrM`.
+fn parse_ext(n: &str) -> Result<BigRational, ALError> {
+    let bad = || ALError::Syntax(format!("bad number {n}"));
+    let int = |s: &str| s.parse::<BigInt>().map_err(|_| bad());
+    let y = match n.split_once('r') {
+        Some((p, q)) => match int(q)? {
+            q if q.is_zero() => return Err(bad()),
+            q => BigRational::new(int(p)?, q),
+        },
+        None => BigRational::from_integer(int(n.strip_suffix('x').unwrap_or(n))?),
+    };
+    Ok(y)
+}
+
+/// Reads the symbol `w` together with the symbols stranded before it, returning their joint span.
+/// A lone symbol is an atom, a strand `` `a`b`c `` a list.
+fn parse_syms((w, span): (&str, Span), words: &mut Vec<(&str, Span)>) -> (Val, Span) {
+    let count = words.iter().rev().take_while(|(w, _)| w.starts_with('`')).count();
+    let mut syms = words.split_off(words.len() - count);
+    syms.push((w, span));
+    let span = syms[0].1.to(span);
+    let mut data = syms.into_iter().map(|(w, _)| intern(&w[1..])).collect_vec();
+    if data.len() == 1 {
+        (Val::Sym(data.remove(0)), span)
+    } else {
+        (Val::SymArr(data.into()), span)
+    }
+}
+
+/// Parses the number `w` together with the numbers stranded before it, returning their joint span.
+/// Integers too large for 64 bits are read as floats, and one extended number makes them all extended.
+fn parse_nums((w, span): (&str, Span), words: &mut Vec<(&str, Span)>) -> Result<(Val, Span), ALError> {
     let mut count = 0;
-    let mut floats = w.contains('.');
     for i in (0..words.len()).rev() {
-        let s = words[i];
-        if matches!(s.as_bytes()[0], b'_' | b'0'..=b'9') {
+        if matches!(words[i].0.as_bytes()[0], b'_' | b'0'..=b'9') {
             count += 1;
-            floats |= s.contains('.');
         } else {
             break;
         }
     }
 
-    if count > 0 {
-        let mut nums = words.split_off(words.len() - count);
-        nums.push(w);
-        let nums: Vec<String> = nums.into_iter()
-            .map(str::to_string)
-            .map(|mut s| unsafe {
-                let n = s.as_bytes_mut() ;
-                if n[0] == b'_' {
-                    n[0] = b'-';
-                }
-                s
-            })
-            .collect();
-
-        fn parse<T: FromStr + Into<Val>>(nums: Vec<String>) -> Val 
-            where Array<T>: Into<Val> + FromIterator<T>,
-            <T as std::str::FromStr>::Err : std::fmt::Debug
-        {
-            nums.into_iter()
-                .map(|n| n.parse::<T>())
-                .collect::<Result<Array<_>, _>>()
-                .unwrap()
-                .into()
-        }
-
-        if floats {
-            parse::<f64>(nums)
+    let mut nums = words.split_off(words.len() - count);
+    nums.push((w, span));
+    let span = nums[0].1.to(span);
+    let nums: Vec<String> = nums.into_iter()
+        .map(|(n, _)| num_text(n))
+        .collect();
+
+    fn parse<T: FromStr + Into<Val>>(nums: &[String]) -> Result<Val, ALError>
+        where Array<T>: Into<Val> + FromIterator<T>,
+    {
+        let mut ys = nums.iter()
+            .map(|n| n.parse::<T>().map_err(|_| ALError::Syntax(format!("bad number {n}"))));
+        if nums.len() == 1 {
+            ys.next().unwrap().map(T::into)
         } else {
-            parse::<i64>(nums)
+            ys.collect::<Result<Array<_>, _>>().map(Array::into)
         }
-    } else if floats {
-        w.parse::<f64>().unwrap().into() 
-    } else {
-        w.parse::<i64>().unwrap().into() 
     }
 
+    let is_float = |s: &String| s.contains('.') || s.contains("inf");
+    let is_ext = |s: &String| s.ends_with('x') || s.contains('r');
+    let y = if nums.iter().any(is_ext) {
+        let mut ys = nums.iter().map(|n| parse_ext(n)).collect::<Result<Vec<_>, _>>()?;
+        if ys.len() == 1 { Val::Ext(ys.remove(0)) } else { Array::from(ys).into() }
+    } else if nums.iter().any(is_float) {
+        parse::<f64>(&nums)?
+    } else {
+        parse::<i64>(&nums).or_else(|_| parse::<f64>(&nums))?
+    };
+    Ok((y, span))
 }
 
 
-fn eval_train(f: Token, g: Token, h: Token) -> Token { 
+/// Builds the explicit definition `m : n` from the body text `n`, closing over the local names in scope.
+fn eval_def(m: Val, n: Val, env: &Env) -> Result<Token, ALError> {
+    let Val::Int(m) = m else {
+        return ALError::as_Domain(format!("explicit definition type must be an int, got {m}"));
+    };
+    let body = String::try_from(n)?;
+    Ok(match m {
+        0 => Token::Noun(body.chars().collect_vec().into()),
+        1 => Token::Adv(Adverb::Expl(Box::new(Explicit::new(m, body, env.capture())))),
+        2 => Token::Conj(Conj::Expl(Box::new(Explicit::new(m, body, env.capture())))),
+        3 | 4 => Token::Verb(Verb::Expl { def: Box::new(Explicit::new(m, body, env.capture())), u: None, v: None }),
+        m => return ALError::as_Domain(format!("bad explicit definition type: {m}")),
+    })
+}
+
+/// Most explicit definitions that may run inside each other, well within the interpreter's stack.
+pub const MAX_DEPTH: usize = 1000;
+
+/// Runs the body of an explicit definition in a fresh scope holding `x`, `y`, `u` and `v`,
+/// whose parent holds the local names the definition closed over.
+/// The result is the value of the last sentence.
+pub fn eval_expl(def: &Explicit, u: Option<Box<Verb>>, v: Option<Box<Verb>>, x: Option<Val>, y: Val, env: &mut Env) -> Result<Val, ALError> {
+    let body = if x.is_some() { &def.dyd } else { &def.mon };
+    if body.is_empty() {
+        let valence = if x.is_some() { "dyadic" } else { "monadic" };
+        return ALError::as_Domain(format!("no {valence} definition in ({def})"));
+    }
+
+    let operand = |u: Box<Verb>| match *u {
+        crate::Verb::Id(n) => *n,
+        u => Val::ValFunc(Func::V(u)),
+    };
+    let mut frame = HashMap::new();
+    frame.insert("y".to_string(), y);
+    if let Some(x) = x {
+        frame.insert("x".to_string(), x);
+    }
+    if let Some(u) = u {
+        frame.insert("u".to_string(), operand(u));
+    }
+    if let Some(v) = v {
+        frame.insert("v".to_string(), operand(v));
+    }
+
+    if env.depth >= MAX_DEPTH {
+        return ALError::as_Limit(format!("stack: more than {MAX_DEPTH} nested calls"));
+    }
+    let scope = Scope::new(frame, def.scope.clone());
+    let caller = env.scope.replace(scope);
+    env.depth += 1;
+    let mut res = Ok(None);
+    for sentence in body {
+        res = eval(sentence, env);
+        if res.is_err() {
+            break;
+        }
+    }
+    env.depth -= 1;
+    env.scope = caller;
+
+    match res? {
+        Some(v) => Ok(v),
+        None => ALError::as_Value(format!("no result from ({def})")),
+    }
+}
+
+fn eval_train(f: Token, g: Token, h: Token) -> Result<Token, ALError> { 
     use Token::*;
     let adv = |t| Adv(Adverb::Train(Box::new(t)));
     let conj = |t| Conj(crate::Conj::Train(Box::new(t)));
 
-    let func = |(f, g, h): (Token, Token, Token)| -> (Func, Func, Func) {
+    let func = |(f, g, h): (Token, Token, Token)| -> Result<(Func, Func, Func), ALError> {
         use Token::*;
         use Func::*;
         let make_func = |t| match t {
-            Verb(v) => V(v),
-            Adv(v) => A(v),
-            Conj(v) => C(v),
-            Noun(n) => V(crate::Verb::Id(Box::new(n))),
-            _ => panic!("not a func {t:?}"),
+            Verb(v) => Ok(V(v)),
+            Adv(v) => Ok(A(v)),
+            Conj(v) => Ok(C(v)),
+            Noun(n) => Ok(V(crate::Verb::Id(Box::new(n)))),
+            t => ALError::as_Syntax(format!("{t} in a train")),
         };
 
-        (make_func(f) , make_func(g), make_func(h))
+        Ok((make_func(f)?, make_func(g)?, make_func(h)?))
     };
 
     let tr = (f, g, h);
-    match &tr {
+    Ok(match &tr {
         //(Verb(_), Noun(g), Conj(_)) => adv(func(tr)),
-        (Adv(_), Verb(_), Verb(_)) => adv(func(tr)),
-        (Adv(_), Adv(_), Adv(_)) => adv(func(tr)),
-        (Noun(_), Conj(_), Adv(_)) => adv(func(tr)), 
-        (Verb(_), Conj(_), Adv(_)) => adv(func(tr)),
-        (Adv(_), Conj(_), Noun(_)) => adv(func(tr)),
-        (Adv(_), Conj(_), Verb(_)) => adv(func(tr)),
-
-        (Verb(_), Verb(_), Conj(_)) => conj(func(tr)),
-        (Noun(_), Verb(_), Conj(_)) => conj(func(tr)),
-        (Conj(_), Verb(_), Verb(_)) => conj(func(tr)), 
-        (Conj(_), Verb(_), Conj(_)) => conj(func(tr)),
-        (Adv(_), Adv(_), Verb(_)) => conj(func(tr)),
-        (Conj(_), Adv(_), Adv(_)) => conj(func(tr)),
-        (Noun(_), Conj(_), Conj(_)) => conj(func(tr)),
-        (Verb(_), Conj(_), Conj(_)) => conj(func(tr)),
-        (Adv(_), Conj(_), Adv(_)) => conj(func(tr)),
-        (Adv(_), Conj(_), Conj(_)) => conj(func(tr)),
-        (Conj(_), Conj(_), Noun(_)) => conj(func(tr)),
-        (Conj(_), Conj(_), Verb(_)) => conj(func(tr)),
-        (Conj(_), Conj(_), Adv(_)) => conj(func(tr)),
-        (Conj(_), Conj(_), Conj(_)) => conj(func(tr)),
-        (f, g, h) => panic!("unhandled train: ({f:?} {g:?} {h:?})")
+        (Adv(_), Verb(_), Verb(_)) => adv(func(tr)?),
+        (Adv(_), Adv(_), Adv(_)) => adv(func(tr)?),
+        (Noun(_), Conj(_), Adv(_)) => adv(func(tr)?), 
+        (Verb(_), Conj(_), Adv(_)) => adv(func(tr)?),
+        (Adv(_), Conj(_), Noun(_)) => adv(func(tr)?),
+        (Adv(_), Conj(_), Verb(_)) => adv(func(tr)?),
+
+        (Verb(_), Verb(_), Conj(_)) => conj(func(tr)?),
+        (Noun(_), Verb(_), Conj(_)) => conj(func(tr)?),
+        (Conj(_), Verb(_), Verb(_)) => conj(func(tr)?), 
+        (Conj(_), Verb(_), Conj(_)) => conj(func(tr)?),
+        (Adv(_), Adv(_), Verb(_)) => conj(func(tr)?),
+        (Conj(_), Adv(_), Adv(_)) => conj(func(tr)?),
+        (Noun(_), Conj(_), Conj(_)) => conj(func(tr)?),
+        (Verb(_), Conj(_), Conj(_)) => conj(func(tr)?),
+        (Adv(_), Conj(_), Adv(_)) => conj(func(tr)?),
+        (Adv(_), Conj(_), Conj(_)) => conj(func(tr)?),
+        (Conj(_), Conj(_), Noun(_)) => conj(func(tr)?),
+        (Conj(_), Conj(_), Verb(_)) => conj(func(tr)?),
+        (Conj(_), Conj(_), Adv(_)) => conj(func(tr)?),
+        (Conj(_), Conj(_), Conj(_)) => conj(func(tr)?),
+        (f, g, h) => return ALError::as_Syntax(format!("unhandled train: ({f} {g} {h})"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Sentences;
+
+    fn run(src: &str) -> Result<Option<Val>, ALError> {
+        SPELL_IN_OUT.get_or_init(SpellInOut::init);
+        let mut env = Env::default();
+        let mut sentences = Sentences::default();
+        let mut res = Ok(None);
+        for line in src.lines() {
+            if let Some(s) = sentences.push(line) {
+                res = Ok(eval(&s, &mut env)?);
+            }
+        }
+        res
+    }
+
+    #[test]
+    fn test_explicit() {
+        let r = run("f =: 3 : 0\n t =: y + 1\n t * 2\n)\nf 1 2 3").unwrap();
+        assert_eq!(r, Some(Val::IntArr(vec![4, 6, 8].into())));
+        let r = run("h =: 3 : 0\n y * 3\n:\n x + y\n)\n2 h 5").unwrap();
+        assert_eq!(r, Some(Val::Int(7)));
+        let r = run("c =: 2 : 'x u y v 1'\n5 + c * 2").unwrap();
+        assert_eq!(r, Some(Val::Int(7)));
+        assert!(run("g =: 4 : 'x - y'\ng 1").is_err());
+    }
+
+    #[test]
+    fn test_scope() {
+        let r = run("t =: 100\nf =: 3 : 't =. y + 1'\nf 1\nt").unwrap();
+        assert_eq!(r, Some(Val::Int(100)));
+        let r = run("f =: 3 : 'g =: y'\nf 1\ng").unwrap();
+        assert_eq!(r, Some(Val::Int(1)));
+        let r = run("adder =: 3 : 0\n n =. y\n 3 : 'y + n'\n)\nadd5 =: adder 5\nn =: 0\nadd5 10").unwrap();
+        assert_eq!(r, Some(Val::Int(15)));
+    }
+
+    #[test]
+    fn test_scope_freed() {
+        SPELL_IN_OUT.get_or_init(SpellInOut::init);
+        let mut env = Env::default();
+        let mut sentences = Sentences::default();
+        // the frame of f holds a local verb and a share of the atoms of a
+        for line in "a =: ! 5\nf =: 3 : 0\n b =. a\n g =. 3 : 'y + b'\n g y\n)\nf 1".lines() {
+            if let Some(s) = sentences.push(line) {
+                eval(&s, &mut env).unwrap();
+            }
+        }
+        let Some(Val::IntArr(a)) = env.names.get("a") else { panic!("a is not a list") };
+        assert!(a.data.is_unique(), "the frame of f outlived its call");
+    }
+
+    #[test]
+    fn test_recursion_limit() {
+        SPELL_IN_OUT.get_or_init(SpellInOut::init);
+        crate::with_stack(|| {
+            let mut env = Env::default();
+            eval("f =: 3 : 'f y'", &mut env).unwrap();
+            let r = eval("f 1", &mut env);
+            assert!(matches!(&r, Err(ALError::At { err, .. }) if matches!(**err, ALError::Limit(_))), "{r:?}");
+            assert_eq!(env.depth, 0);
+            eval("g =: 3 : 'y + 1'", &mut env).unwrap();
+            assert_eq!(eval("g 1", &mut env).unwrap(), Some(Val::Int(2)));
+        });
+    }
+
+    #[test]
+    fn test_trace() {
+        SPELL_IN_OUT.get_or_init(SpellInOut::init);
+        let mut env = Env::default();
+        let mut trace = Trace::new(false);
+        reduce("1 + 2", &mut env, &mut trace).unwrap();
+        assert_eq!(trace.to_string(), "");
+
+        let mut trace = Trace::new(true);
+        reduce("1 + ! 2", &mut env, &mut trace).unwrap();
+        assert_eq!(trace.to_string(), [
+            "┌─────────┬───┬───┬───────┬───────┐",
+            "│ 1 Monad │ ! │ 2 │       │ [0 1] │",
+            "├─────────┼───┼───┼───────┼───────┤",
+            "│ 2 Dyad  │ 1 │ + │ [0 1] │ [1 2] │",
+            "└─────────┴───┴───┴───────┴───────┘",
+        ].map(|l| format!("{l}\n")).concat());
     }
 }
@@ -3,11 +3,11 @@ use itertools::Itertools;
 
 
 #[derive(Debug, Clone, Copy)]
+#[allow(clippy::upper_case_acronyms)]
 enum State {
     SS, // Space
     SX, // Other
     SA, // Alphanumeric
-    ST, // `
     SN, // N
     SNB, // NB
     SNZ, //
@@ -83,7 +83,7 @@ pub fn lex(mut s: &str) -> Vec<&str> {
         }
     }
 
-    for (i, c) in s.chars().enumerate(){
+    for (i, c) in s.char_indices(){
         let cc: CharClass = c.into();
         // X   S   A   N   B   9   D   C   Q
         curs = match curs {
@@ -147,33 +147,38 @@ pub fn lex(mut s: &str) -> Vec<&str> {
             },
             // Z   Z   Z   Z   Z   Z   Z   Z   Z      Z  Trailing Comment
             SZ => SZ,
-            ST => match cc {
-                CS => SS,
-                CB | CA => {j = i; SA},
-                CD => {j = i; SX},
-                CC => {j = i; SX},
-                CX | CN | C9 | CQ | CT => {j = i; id(cc)}
-            },
         }
     }
-    out.push(&s[j..]);
+    if !s.is_empty() {
+        out.push(&s[j..]);
+    }
     out
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_lex() {
-        let r = lex("(+/1 2)  NB. hello   ");
-        assert_eq!(r, vec!["(", "+", "/", "1", "2", ")", "NB. hello"]);
-        let r = lex("' abc 12 +-5::.()'  +:. /. 1.333 _1.2");
-        assert_eq!(r, vec!["' abc 12 +-5::.()'", "+:.", "/.", "1.333", "_1.2"]);
+/// Byte range of a word within its sentence.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
 
+impl Span {
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span { start: self.start.min(other.start), end: self.end.max(other.end) }
     }
 }
 
+/// Like `lex`, with the span of every word in `s`.
+pub fn lex_spans(s: &str) -> Vec<(&str, Span)> {
+    lex(s).into_iter()
+        .map(|w| {
+            let start = s.subslice_offset_stable(w).unwrap();
+            (w, Span { start, end: start + w.len() })
+        })
+        .collect()
+}
+
 pub trait SubsliceOffset {
     /**
     Returns the byte offset of an inner slice relative to an enclosing outer slice.
@@ -203,3 +208,87 @@ impl SubsliceOffset for str {
     }
 
 }
+
+/// True if `words` end in an explicit definition `m : 0`, whose body follows on the next lines.
+pub fn opens_block(words: &[&str]) -> bool {
+    let words = match words.last() {
+        Some(w) if w.starts_with("NB.") => &words[..words.len() - 1],
+        _ => words,
+    };
+    matches!(words, [.., m, ":", "0"] if m.as_bytes()[0].is_ascii_digit())
+}
+
+/// Accumulates source lines into sentences.
+/// The body of an `m : 0` block (every line up to a lone `)`) is folded
+/// into a string literal so the sentence reads as J's one-line `m : '...'` form.
+#[derive(Debug, Default)]
+pub struct Sentences {
+    head: Option<String>,
+    body: Vec<String>,
+    depth: usize,
+}
+
+impl Sentences {
+    pub fn is_open(&self) -> bool {
+        self.head.is_some()
+    }
+
+    pub fn push(&mut self, line: &str) -> Option<String> {
+        let Some(head) = &self.head else {
+            let words = lex(line);
+            if !opens_block(&words) {
+                return Some(line.to_string());
+            }
+            let zero = words.iter().rev().find(|w| **w == "0").unwrap();
+            let off = line.subslice_offset_stable(zero).unwrap();
+            self.head = Some(line[..off].to_string());
+            return None;
+        };
+
+        if line.trim() == ")" {
+            if self.depth == 0 {
+                let body = self.body.drain(..).join("\\n");
+                let sentence = format!("{head}'{body}'");
+                self.head = None;
+                return Some(sentence);
+            }
+            self.depth -= 1;
+        } else if opens_block(&lex(line)) {
+            self.depth += 1;
+        }
+        self.body.push(line.replace('\\', "\\\\").replace('\'', "''"));
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lex() {
+        let r = lex("(+/1 2)  NB. hello   ");
+        assert_eq!(r, vec!["(", "+", "/", "1", "2", ")", "NB. hello"]);
+        let r = lex("' abc 12 +-5::.()'  +:. /. 1.333 _1.2");
+        assert_eq!(r, vec!["' abc 12 +-5::.()'", "+:.", "/.", "1.333", "_1.2"]);
+        assert!(lex("   ").is_empty());
+        let r = lex_spans("  a =: 1 2");
+        assert_eq!(r[1], ("=:", Span { start: 4, end: 6 }));
+        assert_eq!(r[3], ("2", Span { start: 9, end: 10 }));
+
+    }
+
+    #[test]
+    fn test_sentences() {
+        let mut s = Sentences::default();
+        assert_eq!(s.push("1 + 2"), Some("1 + 2".to_string()));
+        assert_eq!(s.push("f =: 3 : 0  NB. def"), None);
+        assert!(s.is_open());
+        assert_eq!(s.push("g =: 1 : 0"), None);
+        assert_eq!(s.push("u y"), None);
+        assert_eq!(s.push(")"), None);
+        assert_eq!(s.push("'it' , y"), None);
+        assert_eq!(s.push(")"), Some("f =: 3 : 'g =: 1 : 0\\nu y\\n)\\n''it'' , y'".to_string()));
+        assert!(!s.is_open());
+    }
+}
@@ -5,7 +5,7 @@ use rustyline::error::ReadlineError;
 use rustyline::{DefaultEditor};
 use colored::Colorize;
 
-use std::{collections::HashMap, fmt::Debug, io::stdout, os::fd::AsRawFd, result};
+use std::{cell::RefCell, collections::HashMap, fmt::{Debug, Display}, rc::Rc, io::stdout, os::fd::AsRawFd, result};
 use std::io::{stderr, Write};
 use std::{iter::Zip, ops::Add, slice::Iter, vec};
 
@@ -15,7 +15,7 @@ use eval::eval;
 use value::*;
 use verb::*;
 
-use crate::lexer::lex;
+use crate::lexer::{Sentences, Span};
 use crate::verb::PrimVerb;
 use itertools::{diff_with, Itertools};
 
@@ -32,20 +32,20 @@ fn main() {
 
     let x = Val::Int(5);
     let xaf = Val::FloatArr(Array {
-        data: vec![1.1, 2.2, 3.3],
+        data: vec![1.1, 2.2, 3.3].into(),
         shape: vec![3],
     });
 
     let yaf = Val::FloatArr(Array {
-        data: vec![4.4, 0.5, 3.5, 4.4, 2.5, 6.6],
+        data: vec![4.4, 0.5, 3.5, 4.4, 2.5, 6.6].into(),
         shape: vec![3, 2],
     });
     let xai = Val::IntArr(Array {
-        data: vec![4, 5, 6, 4, 5, 6],
+        data: vec![4, 5, 6, 4, 5, 6].into(),
         shape: vec![3, 2, 1],
     });
     let ya = Val::IntArr(Array {
-        data: vec![1, 2, 3],
+        data: vec![1, 2, 3].into(),
         shape: vec![3],
     });
     //let rf: Array<f64> = xaf * yaf;
@@ -55,32 +55,95 @@ fn main() {
     //let code = "1+5&:";
     //let words = lexer::lex(code);
     //eval::eval(words);
-    repl().expect("");
+    let mut args = std::env::args().skip(1).peekable();
+    let trace = args.next_if_eq("--trace").is_some();
+    let path = args.next();
+    let args = args.collect_vec();
+    with_stack(|| if let Some(path) = path {
+        if let Err(e) = run_file(&path, args, trace) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    } else {
+        repl(trace).expect("");
+    })
+}
+
+/// Native stack of the interpreter, enough for [`eval::MAX_DEPTH`] nested explicit calls
+/// even in a debug build.
+const STACK_SIZE: usize = 256 << 20;
+
+/// Runs `f` on a thread with a stack of [`STACK_SIZE`].
+pub fn with_stack<R: Send>(f: impl FnOnce() -> R + Send) -> R {
+    std::thread::scope(|s| {
+        std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(s, f)
+            .expect("cannot start the interpreter thread")
+            .join()
+            .unwrap_or_else(|e| std::panic::resume_unwind(e))
+    })
+}
+
+/// Runs every sentence of the script at `path`, with `args` bound to `ARGV` as a boxed list of strings.
+fn run_file(path: &str, args: Vec<String>, trace: bool) -> Result<()> {
+    let src = std::fs::read_to_string(path)?;
+    let mut env = Env { trace, ..Env::default() };
+    let argv: Vec<Val> = args.into_iter()
+        .map(|a| a.chars().collect_vec().into())
+        .collect_vec();
+    env.names.insert("ARGV".to_string(), Array::from(argv).into());
+
+    let mut sentences = Sentences::default();
+    let mut start = 0;
+    for (n, line) in src.lines().enumerate() {
+        if !sentences.is_open() {
+            start = n + 1;
+        }
+        let Some(sentence) = sentences.push(line) else { continue };
+        match eval(&sentence, &mut env) {
+            Err(e) => {
+                eprintln!("{path}:{start}:");
+                return Err(e);
+            },
+            Ok(Some(v)) => println!("{v}"),
+            _ => {},
+        }
+    }
+    if sentences.is_open() {
+        eprintln!("{path}:{start}:");
+        return ALError::as_Syntax("unterminated definition");
+    }
+    Ok(())
 }
 
-fn repl() -> rustyline::Result<()> {
+fn repl(trace: bool) -> rustyline::Result<()> {
     let inp = io::stdin();
     let oerr = io::stderr();
     //let mut buf = String::new();
-    let mut env = Env{
-        names: HashMap::new(),
-        syms: StringInterner::<BucketBackend>::new(),
-    };
+    let mut env = Env { trace, ..Env::default() };
     let mut rl = DefaultEditor::new()?;
+    let mut sentences = Sentences::default();
 
     if rl.load_history("history.txt").is_err() {
         println!("No previous history.");
     }
 
     loop {
-        let readline = rl.readline("\x1b[48;5;46m \x1b[0m");
+        let prompt = if sentences.is_open() { "  " } else { "\x1b[48;5;46m \x1b[0m" };
+        let readline = rl.readline(prompt);
         match readline {
             Ok(line) => {
                 let _ = rl.add_history_entry(line.as_str());
-                let words = lex(&line);
-                println!("lex: {words:?}");
-                match  eval(words, &mut env){
-                    Err(e) => eprintln!("err: {e:?}"),
+                if !sentences.is_open() && line.trim_start().starts_with(')') {
+                    if let Err(e) = command(&line, &mut env) {
+                        eprintln!("{e}");
+                    }
+                    continue;
+                }
+                let Some(sentence) = sentences.push(&line) else { continue };
+                match  eval(&sentence, &mut env){
+                    Err(e) => eprintln!("{e}"),
                     Ok(Some(v)) => println!("{v}"),
                     _ => {},
                 }
@@ -99,14 +162,122 @@ fn repl() -> rustyline::Result<()> {
             }
         }
     }
-    rl.save_history("history.txt");
+    let _ = rl.save_history("history.txt");
+
+    Ok(())
+}
 
+/// Runs a `)name args` session command.
+fn command(line: &str, env: &mut Env) -> Result<()> {
+    let words = line.trim_start()[1..].split_whitespace().collect_vec();
+    match words[..] {
+        ["trace", "on"] => env.trace = true,
+        ["trace", "off"] => env.trace = false,
+        ["trace"] => println!("trace {}", if env.trace { "on" } else { "off" }),
+        _ => return ALError::as_Syntax(format!("unknown command: {}", line.trim())),
+    }
     Ok(())
 }
 
+#[derive(Default)]
 pub struct Env {
+    /// Globals, written by `=:` and by `=.` at the top level.
     pub names: HashMap<String, Val>,
-    pub syms: StringInterner<BucketBackend>,
+    /// Local scope of the running explicit definition, `None` at the top level.
+    pub scope: Option<Scope>,
+    /// Print the reductions of every sentence, set by `--trace` and `)trace on|off`.
+    pub trace: bool,
+    /// Explicit definitions running now, each inside the one before.
+    pub depth: usize,
+}
+
+impl Env {
+    /// Looks `name` up through the scope chain, then in the globals.
+    pub fn get(&self, name: &str) -> Option<Val> {
+        let mut scope = self.scope.clone();
+        while let Some(s) = scope {
+            let s = s.0.borrow();
+            if let Some(y) = s.names.get(name) {
+                return Some(y.clone());
+            }
+            scope = s.parent.clone();
+        }
+        self.names.get(name).cloned()
+    }
+
+    /// Takes out the value `name` holds, if `get` would find it where `assign` writes,
+    /// leaving the caller its only owner.
+    pub fn take(&mut self, name: &str, local: bool) -> Option<Val> {
+        match &self.scope {
+            Some(s) if local => s.0.borrow_mut().names.remove(name),
+            Some(_) if self.scope_has(name) => None,
+            _ => self.names.remove(name),
+        }
+    }
+
+    /// Whether a local scope holds `name`, hiding any global.
+    fn scope_has(&self, name: &str) -> bool {
+        let mut scope = self.scope.clone();
+        while let Some(s) = scope {
+            if s.0.borrow().names.contains_key(name) {
+                return true;
+            }
+            scope = s.0.borrow().parent.clone();
+        }
+        false
+    }
+
+    /// A copy of the local names visible here, for a definition to close over. The copy is never
+    /// written to, so it cannot come to hold the definition and keep its frame alive in a cycle.
+    pub fn capture(&self) -> Option<Scope> {
+        let mut names = HashMap::new();
+        self.scope.as_ref()?;
+        let mut scope = self.scope.clone();
+        while let Some(s) = scope {
+            let s = s.0.borrow();
+            for (name, y) in &s.names {
+                names.entry(name.clone()).or_insert_with(|| y.clone());
+            }
+            scope = s.parent.clone();
+        }
+        Some(Scope::new(names, None))
+    }
+
+    pub fn assign(&mut self, name: String, y: Val, local: bool) {
+        match &self.scope {
+            Some(s) if local => _ = s.0.borrow_mut().names.insert(name, y),
+            _ => _ = self.names.insert(name, y),
+        }
+    }
+}
+
+/// A local scope of an explicit definition.
+/// Definitions made inside it keep a copy of its names, so closures see them after it returns.
+#[derive(Clone)]
+pub struct Scope(Rc<RefCell<Frame>>);
+
+pub struct Frame {
+    pub names: HashMap<String, Val>,
+    pub parent: Option<Scope>,
+}
+
+impl Scope {
+    pub fn new(names: HashMap<String, Val>, parent: Option<Scope>) -> Self {
+        Scope(Rc::new(RefCell::new(Frame { names, parent })))
+    }
+}
+
+impl Debug for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // the frame may hold a definition that holds this scope
+        f.write_str("Scope")
+    }
+}
+
+impl PartialEq for Scope {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
 }
 
 /*
@@ -121,26 +292,85 @@ ioctl_read_bad!(termsize, TIOCGWINSZ, winsize);
 
 #[derive(Debug)]
 pub enum ALError {
-    Syntax,
+    Syntax(String),
+    /// undefined name or a sentence without a value
     Value(String),
-    Type(String),
-    Shape(String),
+    /// argument of the wrong type or outside the verb's domain
+    Domain(String),
+    /// argument of a rank the verb does not accept
+    Rank(String),
+    /// shapes that do not agree
+    Length(String),
+    /// index out of bounds
+    Index(String),
+    /// result too large to build
+    Limit(String),
+    /// not yet implemented
+    Nyi(String),
     IO(io::Error),
+    /// `err` raised at `span` of the sentence `src`.
+    At { src: String, span: Span, err: Box<ALError> },
 }
 
+#[allow(non_snake_case)]
 impl ALError {
-    fn as_Type<T, S: ToString>(msg:S) -> Result<T> {
-        Err(ALError::Type(msg.to_string()))
+    /// Locates the error at `span` of `src`, unless it was already located in an inner sentence.
+    pub fn at(self, src: &str, span: Span) -> Self {
+        match self {
+            e@ALError::At { .. } => e,
+            e => ALError::At { src: src.to_string(), span, err: Box::new(e) },
+        }
+    }
+
+    fn as_Syntax<T, S: ToString>(msg:S) -> Result<T> {
+        Err(ALError::Syntax(msg.to_string()))
     }
     fn as_Value<T, S: ToString>(msg: S) -> Result<T> {
-        Err(ALError::Type(msg.to_string()))
+        Err(ALError::Value(msg.to_string()))
+    }
+    fn as_Domain<T, S: ToString>(msg:S) -> Result<T> {
+        Err(ALError::Domain(msg.to_string()))
+    }
+    fn as_Rank<T, S: ToString>(msg:S) -> Result<T> {
+        Err(ALError::Rank(msg.to_string()))
+    }
+    fn as_Length<T, S: ToString>(msg: S) -> Result<T> {
+        Err(ALError::Length(msg.to_string()))
     }
-    fn as_Shape<T, S: ToString>(msg: S) -> Result<T> {
-        Err(ALError::Shape(msg.to_string()))
+    fn as_Index<T, S: ToString>(msg: S) -> Result<T> {
+        Err(ALError::Index(msg.to_string()))
+    }
+    fn as_Limit<T, S: ToString>(msg: S) -> Result<T> {
+        Err(ALError::Limit(msg.to_string()))
+    }
+    fn as_Nyi<T, S: ToString>(msg: S) -> Result<T> {
+        Err(ALError::Nyi(msg.to_string()))
     }
 }
 
 
+impl Display for ALError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use ALError::*;
+        match self {
+            Syntax(m) => f.write_fmt(format_args!("|syntax error: {m}")),
+            Value(m) => f.write_fmt(format_args!("|value error: {m}")),
+            Domain(m) => f.write_fmt(format_args!("|domain error: {m}")),
+            Rank(m) => f.write_fmt(format_args!("|rank error: {m}")),
+            Length(m) => f.write_fmt(format_args!("|length error: {m}")),
+            Index(m) => f.write_fmt(format_args!("|index error: {m}")),
+            Limit(m) => f.write_fmt(format_args!("|limit error: {m}")),
+            Nyi(m) => f.write_fmt(format_args!("|nonce error: {m}")),
+            IO(e) => f.write_fmt(format_args!("|file error: {e}")),
+            At { src, span, err } => {
+                let pad = src[..span.start].chars().count();
+                let width = src[span.start..span.end].chars().count().max(1);
+                f.write_fmt(format_args!("{err}\n|   {src}\n|   {}{}", " ".repeat(pad), "^".repeat(width)))
+            },
+        }
+    }
+}
+
 type Result<T> = result::Result<T, ALError>;
 
 impl From<io::Error> for ALError {
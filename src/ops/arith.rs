@@ -1,31 +1,53 @@
 
 
-use super::super::ALError;
+use crate::{ALError, Result};
 use itertools::Itertools;
+use std::cmp::Ordering;
 use std::ops::*;
 
-use crate::{Array, Val};
+use crate::{atom_count, Array, Val};
+use super::cells::Cells;
+use super::shape_matches;
+use super::math::{floats, ints};
+use super::shape::Shape;
+use super::sort::keys;
+
 pub trait Til {
-    fn til_mon(y: Val) -> Val;
-    fn til_dyd(x: Val, y: Val) -> Val;
+    fn til_mon(y: Val) -> Result<Val>;
+    fn til_dyd(x: Val, y: Val) -> Result<Val>;
+    fn steps(y: Val) -> Result<Val>;
 }
 
 
 
 impl Til for Val {
-    fn til_dyd(x: Val, y: Val) -> Val {
-        panic!("nyi");
+    fn til_dyd(x: Val, y: Val) -> Result<Val> {
+        ALError::as_Nyi("dyadic !")
     }
 
-    fn til_mon(y: Val) -> Val {
+    /// `i: n` counts from `-n` to `n`.
+    fn steps(y: Val) -> Result<Val> {
+        match y {
+            Val::Int(n) if atom_count([n.unsigned_abs().saturating_mul(2).saturating_add(1)]).is_err() =>
+                ALError::as_Limit(format!("too many steps: {n}")),
+            Val::Int(n) if n < 0 => Ok(Array::from((n..=-n).rev().collect_vec()).into()),
+            Val::Int(n) => Ok(Array::from((-n..=n).collect_vec()).into()),
+            y => ALError::as_Domain(format!("cannot step through {y}")),
+        }
+    }
+
+    fn til_mon(y: Val) -> Result<Val> {
         use Val::*;
         match y {
-            Int(y) => til(vec![y as u32], 0..y),
-            IntArr(Array { data, shape }) =>  til(
-                data.iter().map(|i| *i as u32).collect_vec(),
-                0..data.into_iter().product()
-            ),
-            _ => panic!("cannot til {y:?}")
+            Int(y) if y < 0 => ALError::as_Domain(format!("cannot til negative {y}")),
+            Int(y) => Ok(til(vec![y as u32], 0..atom_count([y as u64])? as i64)),
+            IntArr(Array { data, shape }) if shape.len() > 1 => ALError::as_Rank("til of a table"),
+            IntArr(Array { data, shape }) if data.iter().any(|i| *i < 0) => ALError::as_Domain("cannot til negative shape"),
+            IntArr(Array { data, shape }) =>  {
+                let n = atom_count(data.iter().map(|&i| i as u64))?;
+                Ok(til(data.iter().map(|&i| i as u32).collect_vec(), 0..n as i64))
+            },
+            _ => ALError::as_Domain(format!("cannot til {y}"))
             
         }
     }
@@ -33,48 +55,273 @@ impl Til for Val {
 
 fn til(shape: Vec<u32>, r: Range<i64>) -> Val {
     Array {         
-        data: r.collect_vec(),
+        data: r.collect(),
         shape, 
     }.into()
 }
 
+/// Applies `f` between the atoms of `x` and `y` under leading axis agreement,
+/// recursing into boxed values and keeping their nesting. Results of flat arguments
+/// are laid out as a flat array of their type.
+pub(super) fn pervade(x: Val, y: Val, f: impl Fn(Val, Val) -> Result<Val>) -> Result<Val> {
+    use Val::*;
+    let flat = !boxed(&x) && !boxed(&y);
+    let boxed = matches!(x, Unit(_)) || matches!(y, Unit(_));
+    let atoms = |v: Val| match v {
+        Unit(b) => Ok((Vec::new(), vec![*b])),
+        v => v.split(0),
+    };
+    let (xs, xa) = atoms(x)?;
+    let (ys, ya) = atoms(y)?;
+    if !(xs.starts_with(&ys) || ys.starts_with(&xs)) {
+        return ALError::as_Length(format!("shapes {xs:?} and {ys:?} dont agree"));
+    }
+    let shape = if xs.len() > ys.len() { xs } else { ys };
+    let n = if xa.is_empty() || ya.is_empty() { 0 } else { xa.len().max(ya.len()) };
+    let (kx, ky) = (n / xa.len().max(1), n / ya.len().max(1));
+    let mut data = (0..n)
+        .map(|i| {
+            let (a, b) = (xa[i / kx].clone(), ya[i / ky].clone());
+            // the contents of boxes meet here without having been checked
+            shape_matches(&a, &b).map_or_else(|| f(a, b), Err)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(if shape.is_empty() && boxed && data.len() == 1 {
+        Unit(Box::new(data.remove(0)))
+    } else if flat {
+        return Val::assemble(shape, data);
+    } else {
+        Array { data: data.into(), shape }.into()
+    })
+}
+
+/// Pairs the atoms of `x` and `y` under leading axis agreement:
+/// each atom of the lower rank side meets a whole cell of the other.
+pub(super) fn agree<T: Copy, R>(x: &Array<T>, y: &Array<T>, mut f: impl FnMut(T, T) -> R) -> Result<Array<R>> {
+    let cell = |long: &[u32], short: &[u32]| Ord::max(long[short.len()..].iter().product::<u32>(), 1) as usize;
+    let mut data = Vec::with_capacity(x.data.len().max(y.data.len()));
+    let shape = if x.shape == y.shape {
+        data.extend(x.data.iter().zip(&y.data).map(|(a, b)| f(*a, *b)));
+        x.shape.clone()
+    } else if y.shape.starts_with(&x.shape) {
+        for (a, yc) in x.data.iter().zip(y.data.chunks_exact(cell(&y.shape, &x.shape))) {
+            data.extend(yc.iter().map(|b| f(*a, *b)));
+        }
+        y.shape.clone()
+    } else if x.shape.starts_with(&y.shape) {
+        for (xc, b) in x.data.chunks_exact(cell(&x.shape, &y.shape)).zip(&y.data) {
+            data.extend(xc.iter().map(|a| f(*a, *b)));
+        }
+        x.shape.clone()
+    } else {
+        return ALError::as_Length(format!("shapes {:?} and {:?} dont agree", x.shape, y.shape));
+    };
+    Ok(Array { data: data.into(), shape })
+}
+
+/// Runs the integer op `f`, redoing it in floats with `g` when any result overflows.
+fn promote(x: Val, y: Val, f: fn(i64, i64) -> Option<i64>, g: fn(Val, Val) -> Result<Val>) -> Result<Val> {
+    use Val::*;
+    // an array with an atom is written over when nothing else shares it and nothing overflows
+    let (x, y) = match (x, y) {
+        (IntArr(mut a), Int(b)) => match in_place(&mut a, |i| f(i, b)) {
+            true => return Ok(IntArr(a)),
+            false => (IntArr(a), Int(b)),
+        },
+        (Int(a), IntArr(mut b)) => match in_place(&mut b, |i| f(a, i)) {
+            true => return Ok(IntArr(b)),
+            false => (Int(a), IntArr(b)),
+        },
+        xy => xy,
+    };
+    let ints = |v: Val| match v {
+        Int(i) => Ok(Array { data: vec![i].into(), shape: Vec::new() }),
+        IntArr(a) => Ok(a),
+        v => ALError::as_Domain(format!("not an integer: {v}")),
+    };
+    let (x, y) = (ints(x)?, ints(y)?);
+    let r = agree(&x, &y, f)?;
+    if let Some(data) = r.data.iter().copied().collect::<Option<Vec<_>>>() {
+        Ok(match r.shape.is_empty() {
+            true => Int(data[0]),
+            false => IntArr(Array { data: data.into(), shape: r.shape }),
+        })
+    } else {
+        let float = |a: Array<i64>| match a.shape.is_empty() {
+            true => Float(a.data[0] as f64),
+            false => FloatArr(a.into()),
+        };
+        g(float(x), float(y))
+    }
+}
+
+/// Replaces each atom of `a` with `f` of it, if `a` is the only owner of its atoms
+/// and `f` gives a result for every one.
+fn in_place(a: &mut Array<i64>, f: impl Fn(i64) -> Option<i64>) -> bool {
+    if !a.data.is_unique() || a.data.iter().any(|&i| f(i).is_none()) {
+        return false;
+    }
+    a.data.iter_mut().for_each(|i| *i = f(*i).unwrap_or(*i));
+    true
+}
+
+fn boxed(y: &Val) -> bool {
+    matches!(y, Val::ValArr(_) | Val::Unit(_))
+}
+
+fn sym(y: &Val) -> bool {
+    matches!(y, Val::Sym(_) | Val::SymArr(_))
+}
+
+/// Runs the op `op` where one side is symbols, which only compare: by name with each other,
+/// and after numbers and characters otherwise.
+fn symbols(op: &str, x: Val, y: Val) -> Result<Val> {
+    let test: fn(Ordering) -> bool = match op {
+        "eq" => Ordering::is_eq,
+        "lt" => Ordering::is_lt,
+        "gt" => Ordering::is_gt,
+        "le" => Ordering::is_le,
+        "ge" => Ordering::is_ge,
+        op => return ALError::as_Domain(format!("cannot {op} symbols")),
+    };
+    let (kx, ky) = (keys(&x)?, keys(&y)?);
+    let at = |v: &Val, n: usize| Array { data: (0..n).collect(), shape: v.shape_ref().clone() };
+    let r = agree(&at(&x, kx.len()), &at(&y, ky.len()), |a, b| test(kx[a].cmp(&ky[b])) as i64)?;
+    Ok(if r.shape.is_empty() { Val::Int(r.data[0]) } else { r.into() })
+}
+
+/// Runs the op `op` where one side is text, the BQN way: a character moves by a number
+/// and two characters differ by one, while comparisons order every number before every character.
+fn text(op: &str, x: Val, y: Val) -> Result<Val> {
+    let (xc, yc) = (x.is_text(), y.is_text());
+    let ordered = matches!(op, "eq" | "lt" | "gt" | "le" | "ge");
+    let side = |v: Val| -> Result<Array<i64>> {
+        Ok(match v {
+            v@(Int(_) | IntArr(_)) => ints(v)?,
+            // the order of a character and a number never depends on the number
+            v@(Float(_) | FloatArr(_) | Ext(_) | ExtArr(_)) if ordered => {
+                let Array { data, shape } = floats(v)?;
+                Array { data: vec![0; data.len()].into(), shape }
+            },
+            v if v.is_text() => v.code_points().unwrap_or_default().cast(),
+            v => return ALError::as_Domain(format!("cannot {op} {v} and text")),
+        })
+    };
+    let kind = |c: bool| if c { "characters" } else { "numbers" };
+    let (x, y) = (side(x)?, side(y)?);
+    let chars = |a: Array<i64>| -> Result<Val> {
+        match a.data.iter().map(|&c| u32::try_from(c).ok().filter(|&c| c <= 0x10FFFF)).collect() {
+            Some(data) => Ok(Val::chars(Array { data, shape: a.shape })),
+            None => ALError::as_Domain(format!("{op} leaves the range of characters")),
+        }
+    };
+    let number = |a: Array<i64>| if a.shape.is_empty() { Int(a.data[0]) } else { IntArr(a) };
+    use Val::*;
+    match (op, xc, yc) {
+        ("add", true, false) | ("add", false, true) => chars(agree(&x, &y, i64::saturating_add)?),
+        ("sub", true, false) => chars(agree(&x, &y, i64::saturating_sub)?),
+        ("sub", true, true) => Ok(number(agree(&x, &y, |a, b| a - b)?)),
+        ("min", true, true) => chars(agree(&x, &y, Ord::min)?),
+        ("max", true, true) => chars(agree(&x, &y, Ord::max)?),
+        (op, xc, yc) if ordered => {
+            let cmp = |a: i64, b: i64| (xc, a).cmp(&(yc, b));
+            let test: fn(Ordering) -> bool = match op {
+                "eq" => |o| o.is_eq(),
+                "lt" => |o| o.is_lt(),
+                "gt" => |o| o.is_gt(),
+                "le" => |o| o.is_le(),
+                _ => |o| o.is_ge(),
+            };
+            Ok(number(agree(&x, &y, |a, b| test(cmp(a, b)) as i64)?))
+        },
+        (op, xc, yc) => ALError::as_Domain(format!("cannot {op} {} and {}", kind(xc), kind(yc))),
+    }
+}
+
+/// Extended precision arithmetic on rationals, exact where the result is rational.
+pub(super) mod ext {
+    use num::{BigInt, BigRational, ToPrimitive, Zero};
+    use crate::Val;
+
+    pub fn of(i: i64) -> BigRational {
+        BigRational::from_integer(BigInt::from(i))
+    }
+
+    pub fn float(x: &BigRational) -> f64 {
+        x.to_f64().unwrap_or(f64::NAN)
+    }
+
+    pub fn add(x: BigRational, y: BigRational) -> Val { Val::Ext(x + y) }
+    pub fn sub(x: BigRational, y: BigRational) -> Val { Val::Ext(x - y) }
+    pub fn mul(x: BigRational, y: BigRational) -> Val { Val::Ext(x * y) }
+    pub fn max(x: BigRational, y: BigRational) -> Val { Val::Ext(Ord::max(x, y)) }
+    pub fn min(x: BigRational, y: BigRational) -> Val { Val::Ext(Ord::min(x, y)) }
+    pub fn eq(x: BigRational, y: BigRational) -> Val { Val::Int((x == y) as i64) }
+    pub fn ge(x: BigRational, y: BigRational) -> Val { Val::Int((x >= y) as i64) }
+    pub fn gt(x: BigRational, y: BigRational) -> Val { Val::Int((x > y) as i64) }
+    pub fn lt(x: BigRational, y: BigRational) -> Val { Val::Int((x < y) as i64) }
+    pub fn le(x: BigRational, y: BigRational) -> Val { Val::Int((x <= y) as i64) }
+
+    pub fn div(x: BigRational, y: BigRational) -> Val {
+        if y.is_zero() {
+            Val::Float(float(&x) / 0.0)
+        } else {
+            Val::Ext(x / y)
+        }
+    }
+}
+
 macro_rules! impl_op {
-    ( $($name:ident-$fn:ident);+) => {
+    ( $($name:ident-$fn:ident $(-$checked:ident)?);+) => {
         $(impl $name for Val  {
-    type Output = Val;
+    type Output = Result<Val>;
+    // ops with a checked integer form shadow the plain integer arms, promoting on overflow
+    #[allow(unreachable_patterns)]
     fn $fn(self, rhs: Self) -> Self::Output {
         use Val::*;
-        match (self, rhs) {
+        Ok(match (self, rhs) {
+            // symbols come first so that they order after characters just as after numbers
+            (x, y) if sym(&x) && !boxed(&y) || sym(&y) && !boxed(&x) => return symbols(stringify!($fn), x, y),
+            (x, y) if x.is_text() && !boxed(&y) || y.is_text() && !boxed(&x) => return text(stringify!($fn), x, y),
+            $((x@(Int(_) | IntArr(_)), y@(Int(_) | IntArr(_))) => return promote(x, y, i64::$checked, <Val as $name>::$fn),)?
+            (Ext(x), Ext(y)) => ext::$fn(x, y),
+            (Ext(x), Int(y)) => ext::$fn(x, ext::of(y)),
+            (Int(x), Ext(y)) => ext::$fn(ext::of(x), y),
+            (Ext(x), Float(y)) => return $name::$fn(Float(ext::float(&x)), Float(y)),
+            (Float(x), Ext(y)) => return $name::$fn(Float(x), Float(ext::float(&y))),
+            (x@(Ext(_) | ExtArr(_)), y) | (x, y@(Ext(_) | ExtArr(_))) => return pervade(x, y, <Val as $name>::$fn),
              (Int(x), Int(y)) => Int($name::$fn(x, y)),
              (Int(x), Float(y)) => Float((x as f64).$fn(y)),
              (Float(x), Int(y)) => Float(x.$fn(y as f64)),
              (Float(x), Float(y)) => Float(x.$fn(y)),
-             (IntArr(x), IntArr(y)) => IntArr(x.$fn(y)),
-            (FloatArr(x), FloatArr(y)) => FloatArr(x.$fn(y)),
+             (IntArr(x), IntArr(y)) => IntArr(x.$fn(y)?),
+            (FloatArr(x), FloatArr(y)) => FloatArr(x.$fn(y)?),
 
             (Int(x), IntArr(y)) => IntArr($name::$fn(x, y)),
             (IntArr(x), Int(y)) => IntArr(x.$fn(y)),
 
-            (Int(x), FloatArr(y)) => FloatArr(Array::<f64>::from(x).$fn(y)),
-            (FloatArr(x), Int(y)) => FloatArr(x.$fn(Array::<f64>::from(y))),
+            (Int(x), FloatArr(y)) => FloatArr($name::$fn(x as f64, y)),
+            (FloatArr(x), Int(y)) => FloatArr($name::$fn(x, y as f64)),
+            (Float(x), FloatArr(y)) => FloatArr($name::$fn(x, y)),
+            (FloatArr(x), Float(y)) => FloatArr($name::$fn(x, y)),
 
-        (Float(x), IntArr(y)) => FloatArr(Array::<f64>::from(x).$fn(Array::<f64>::from(y))),
+        (Float(x), IntArr(y)) => FloatArr($name::$fn(x, Array::<f64>::from(y))),
         (IntArr(x), Float(y)) => FloatArr(Array::<f64>::from(x).$fn(y)),
 
-        (IntArr(x), FloatArr(y)) => FloatArr(Array::<f64>::from(x).$fn(y)),
-        (FloatArr(x), IntArr(y)) => FloatArr(x.$fn(Array::<f64>::from(y))),
-        (AsciiArr(x), AsciiArr(y)) => IntArr(x.$fn(y).cast::<i64>()),
-        (x, y) => unreachable!("nyi: {:?} op {:?}", x, y),
-        }
+        (IntArr(x), FloatArr(y)) => FloatArr(Array::<f64>::from(x).$fn(y)?),
+        (FloatArr(x), IntArr(y)) => FloatArr(x.$fn(Array::<f64>::from(y))?),
+        (x@(ValArr(_) | Unit(_)), y) | (x, y@(ValArr(_) | Unit(_))) => return pervade(x, y, <Val as $name>::$fn),
+        (x, y) => return ALError::as_Nyi(format!("{} {} {}", x, stringify!($fn), y)),
+        })
         }
         })+
     };
 }
 
 impl_op!(
-    Add-add;
-    Sub-sub;
-    Mul-mul;
+    Add-add-checked_add;
+    Sub-sub-checked_sub;
+    Mul-mul-checked_mul;
     //Div-div;
     Max-max;
     Min-min;
@@ -86,24 +333,36 @@ impl_op!(
 );
 
 impl Div for Val  {
-    type Output = Val;
+    type Output = Result<Val>;
     fn div(self, rhs: Self) -> Self::Output {
         use Val::*;
-        println!("{:?} div {:?}", self, rhs);
         let floatify = |x| match x {
-            Int(x) => Float(x as f64),
-            IntArr(x) => FloatArr(Array::<f64>::from(x)),
-            FloatArr(_) | Float(_) => x,
-            x => unreachable!("nyi: {:?} div y", x),
+            Int(x) => Ok(Float(x as f64)),
+            IntArr(x) => Ok(FloatArr(Array::<f64>::from(x))),
+            FloatArr(_) | Float(_) => Ok(x),
+            x => ALError::as_Nyi(format!("{x} div")),
         };
 
-        match (floatify(self), floatify(rhs)) {
-            (Float(x), Float(y)) => Float(x / y),
-            (FloatArr(x), Float(y)) => FloatArr(x / y),
-            (Float(x), FloatArr(y)) => FloatArr(x / y),
-            (FloatArr(x), FloatArr(y)) => FloatArr(x / y),
-            _ => unreachable!("no"),
-        }
+        Ok(match (self, rhs) {
+            // symbols come first so that they order after characters just as after numbers
+            (x, y) if sym(&x) && !boxed(&y) || sym(&y) && !boxed(&x) => return symbols("div", x, y),
+            (x, y) if x.is_text() && !boxed(&y) || y.is_text() && !boxed(&x) => return text("div", x, y),
+            (Ext(x), Ext(y)) => ext::div(x, y),
+            (Ext(x), Int(y)) => ext::div(x, ext::of(y)),
+            (Int(x), Ext(y)) => ext::div(ext::of(x), y),
+            (Ext(x), y@(Float(_) | FloatArr(_))) => return Float(ext::float(&x)) / y,
+            (x@(Float(_) | FloatArr(_)), Ext(y)) => return x / Float(ext::float(&y)),
+            (x@(ValArr(_) | Unit(_) | Ext(_) | ExtArr(_)), y) | (x, y@(ValArr(_) | Unit(_) | Ext(_) | ExtArr(_))) => {
+                return pervade(x, y, <Val as Div>::div)
+            },
+            (x, y) => match (floatify(x)?, floatify(y)?) {
+                (Float(x), Float(y)) => Float(x / y),
+                (FloatArr(x), Float(y)) => FloatArr(x / y),
+                (Float(x), FloatArr(y)) => FloatArr(x / y),
+                (FloatArr(x), FloatArr(y)) => FloatArr((x / y)?),
+                (x, y) => return ALError::as_Domain(format!("cannot divide {x} by {y}")),
+            },
+        })
     }
 }
 
@@ -225,14 +484,9 @@ macro_rules! impl_arr_prim_op {
         $(
         impl $name<Array<$tp>> for $tp {
             type Output = Array<$tp>;
-            fn $fn(self, Array { data, shape }: Array<$tp>) -> Self::Output {
-                Array {
-                    data: data
-                        .into_iter()
-                        .map(|x| <$tp as $name>::$fn(self, x))
-                        .collect_vec(),
-                    shape,
-                }
+            fn $fn(self, mut y: Array<$tp>) -> Self::Output {
+                y.data.iter_mut().for_each(|x| *x = <$tp as $name>::$fn(self, *x));
+                y
             }
         })+
     };
@@ -240,52 +494,13 @@ macro_rules! impl_arr_prim_op {
 
 macro_rules! impl_arr_op {
     ( $($name:ident-$fn:ident);+) => {
-        $( impl<T: $name<T, Output = T> + std::fmt::Debug + ArrayOps<T>> $name<Array<T>> for Array<T>
+        $( impl<T: $name<T, Output = T> + std::fmt::Debug + ArrayOps<T> + Copy> $name<Array<T>> for Array<T>
         where
             for<'a> Array<T>: From<T> + From<&'a [T]>,
         {
-            type Output = Self;
+            type Output = Result<Self>;
             fn $fn(self, rhs: Self) -> Self::Output {
-                let mut y = rhs;
-                let x = self;
-                let opt = x
-                    .shape
-                    .iter()
-                    .zip(&y.shape)
-                    .find_position(|(x, y)| *x != *y);
-                if let Some((rank, (xl, yl))) = opt {
-                    //let len = x.shape[0..rank].iter().fold(1, |a, x| a * x);
-                    let len_a = x.shape[rank..].iter().fold(1, |a, x| a * x);
-                    let len_b = y.shape[rank..].iter().fold(1, |a, x| a * x);
-                    if len_a == 1 {
-                        y.data = x
-                            .data
-                            .into_iter()
-                            .zip(y.data.chunks_exact(len_b as usize))
-                            .flat_map(|(x, yc)| <T as ArrayOps<T>>::$fn(x, Array::<T>::from(yc)))
-                            .collect_vec();
-                    } else if len_b == 1 {
-                        y.data = x
-                            .data
-                            .chunks_exact(len_a as usize)
-                            .into_iter()
-                            .zip(y.data.into_iter())
-                            .flat_map(|(xc, yv)| Array::<T>::$fn(xc.into(), yv.into()))
-                            .collect();
-                        y.shape = x.shape
-                    } else {
-                        dbg!(opt);
-                        unreachable!("eql len");
-                    }
-                } else {
-                    y.data = x
-                        .data
-                        .into_iter()
-                        .zip(y.data.into_iter())
-                        .map(|(x, y)| <T as $name>::$fn(x, y))
-                        .collect_vec();
-                }
-                return y;
+                agree(&self, &rhs, <T as $name>::$fn)
             }
         }
 
@@ -293,11 +508,9 @@ macro_rules! impl_arr_op {
 
         impl<T: $name<Output = T> + Copy> $name<T> for Array<T> {
             type Output = Self;
-            fn $fn(self, y: T) -> Self::Output {
-                Array {
-                    data: self.data.into_iter().map(|x| T::$fn(x, y)).collect_vec(),
-                    shape: self.shape,
-                }
+            fn $fn(mut self, y: T) -> Self::Output {
+                self.data.iter_mut().for_each(|x| *x = T::$fn(*x, y));
+                self
             }
         })+
     };
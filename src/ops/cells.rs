@@ -0,0 +1,261 @@
+use itertools::{repeat_n, Itertools};
+
+use crate::{atom_count, ALError, Array, Env, Fill, Result, Val, Verb};
+
+use super::arith::ext;
+use super::shape::{list, Shape};
+use super::{eval_dyd, eval_mon};
+
+pub trait Cells where Self: Sized {
+    /// Splits into cells of rank `r`, along with the frame they are laid out in.
+    fn split(self, r: usize) -> Result<(Vec<u32>, Vec<Self>)>;
+    /// Lays results out in `frame`, padding them with fill to a common shape.
+    fn assemble(frame: Vec<u32>, cells: Vec<Self>) -> Result<Self>;
+    fn rank_mon(u: Verb, r: i64, y: Self, env: &mut Env) -> Result<Self>;
+    fn rank_dyd(u: Verb, l: i64, r: i64, x: Self, y: Self, env: &mut Env) -> Result<Self>;
+}
+
+/// The rank of the cells a verb of rank `r` sees in an argument of rank `rank`,
+/// negative ranks counting down from the argument's.
+pub fn cell_rank(r: i64, rank: usize) -> usize {
+    if r >= 0 {
+        rank.min(r as usize)
+    } else {
+        rank.saturating_sub(r.unsigned_abs() as usize)
+    }
+}
+
+fn split<T: Clone + Into<Val>>(a: Array<T>, r: usize) -> Result<(Vec<u32>, Vec<Val>)>
+where Array<T>: Into<Val>
+{
+    let (frame, cells) = a.cells(r)?;
+    let cells = cells.into_iter()
+        .map(|c| if c.shape.is_empty() && c.data.len() == 1 {
+            c.data[0].clone().into()
+        } else {
+            c.into()
+        })
+        .collect_vec();
+    Ok((frame, cells))
+}
+
+/// `a` raised to the rank of `shape` by leading unit axes, then padded with its fill out to `shape`.
+fn pad<T: Fill + Clone + Into<Val>>(a: Array<T>, shape: &[u32]) -> Val
+where Array<T>: Into<Val>
+{
+    let own = repeat_n(1, shape.len() - a.rank()).chain(a.shape.iter().copied()).collect_vec();
+    let mut data = vec![a.fill(); shape.iter().product::<u32>() as usize];
+    for (k, y) in a.data.iter().enumerate() {
+        // walk the axes from the last, moving atom `k` from its own strides to those of `shape`
+        let (mut k, mut at, mut step) = (k, 0, 1);
+        for (&n, &m) in own.iter().zip(shape).rev() {
+            at += k % n as usize * step;
+            k /= n as usize;
+            step *= m as usize;
+        }
+        data[at] = y.clone();
+    }
+    Array { data: data.into(), shape: shape.to_vec() }.into()
+}
+
+/// A cell of rank `r` of `a` made of its fill.
+fn fill_cell<T: Fill + Clone + Into<Val>>(a: &Array<T>, r: usize) -> Val
+where Array<T>: Into<Val>
+{
+    let shape = a.shape[a.rank() - r..].to_vec();
+    let n = shape.iter().product::<u32>() as usize;
+    let fill = a.fill();
+    if shape.is_empty() {
+        fill.into()
+    } else {
+        Array { data: vec![fill; n].into(), shape }.into()
+    }
+}
+
+/// The results of a verb over the empty `frame`. As in J, running it on a cell of fills
+/// tells the shape and type of the results, which are numbers if it fails.
+fn no_cells(frame: Vec<u32>, res: Result<Val>) -> Val {
+    let proto = res.unwrap_or(Val::Int(0));
+    let shape = frame.into_iter().chain(proto.shape_ref().iter().copied()).collect();
+    proto.empty(shape)
+}
+
+impl Val {
+    fn fill_cell(&self, r: usize) -> Val {
+        use Val::*;
+        match self {
+            IntArr(a) => fill_cell(a, r),
+            FloatArr(a) => fill_cell(a, r),
+            ExtArr(a) => fill_cell(a, r),
+            AsciiArr(a) => fill_cell(a, r),
+            Utf16Arr(a) => fill_cell(a, r),
+            Utf32Arr(a) => fill_cell(a, r),
+            ValArr(a) => fill_cell(a, r),
+            SymArr(a) => fill_cell(a, r),
+            y => y.clone(),
+        }
+    }
+
+    fn pad(self, shape: &[u32]) -> Val {
+        use Val::*;
+        match list(self) {
+            IntArr(a) => pad(a, shape),
+            FloatArr(a) => pad(a, shape),
+            ExtArr(a) => pad(a, shape),
+            AsciiArr(a) => pad(a, shape),
+            Utf16Arr(a) => pad(a, shape),
+            Utf32Arr(a) => pad(a, shape),
+            ValArr(a) => pad(a, shape),
+            SymArr(a) => pad(a, shape),
+            y => y,
+        }
+    }
+}
+
+impl Cells for Val {
+    fn split(self, r: usize) -> Result<(Vec<u32>, Vec<Val>)> {
+        use Val::*;
+        match self {
+            IntArr(a) => split(a, r),
+            FloatArr(a) => split(a, r),
+            ExtArr(a) => split(a, r),
+            AsciiArr(a) => split(a, r),
+            Utf16Arr(a) => split(a, r),
+            Utf32Arr(a) => split(a, r),
+            ValArr(a) => split(a, r),
+            SymArr(a) => split(a, r),
+            y => Ok((Vec::new(), vec![y])),
+        }
+    }
+
+    fn assemble(frame: Vec<u32>, mut cells: Vec<Val>) -> Result<Val> {
+        use Val::*;
+        if frame.is_empty() && cells.len() == 1 {
+            return Ok(cells.remove(0));
+        }
+        if cells.is_empty() {
+            return Ok(Array { data: Vec::<i64>::new().into(), shape: frame }.into());
+        }
+        // shorter results count as having leading axes of 1, and each axis is as long as its longest
+        let rank = cells.iter().map(|c| c.shape_ref().len()).max().unwrap_or(0);
+        let cell_shape = (0..rank)
+            .map(|i| cells.iter()
+                .map(|c| {
+                    let s = c.shape_ref();
+                    (i + s.len()).checked_sub(rank).map_or(1, |j| s[j])
+                })
+                .max()
+                .unwrap_or(1))
+            .collect_vec();
+        if cells.iter().any(|c| *c.shape_ref() != cell_shape) {
+            atom_count(frame.iter().chain(&cell_shape).map(|&n| n as u64))?;
+            cells = cells.into_iter()
+                .map(|c| if *c.shape_ref() == cell_shape { c } else { c.pad(&cell_shape) })
+                .collect();
+        }
+        let shape = frame.into_iter().chain(cell_shape.iter().copied()).collect_vec();
+
+        if cells.iter().all(|c| matches!(c, Int(_) | IntArr(_))) {
+            let data = cells.into_iter()
+                .flat_map(|c| match c {
+                    Int(y) => vec![y],
+                    IntArr(a) => a.data.into_vec(),
+                    _ => Vec::new(),
+                })
+                .collect_vec();
+            Ok(Array { data: data.into(), shape }.into())
+        } else if cells.iter().all(|c| matches!(c, Int(_) | IntArr(_) | Ext(_) | ExtArr(_))) {
+            let data = cells.into_iter()
+                .flat_map(|c| match c {
+                    Int(y) => vec![ext::of(y)],
+                    IntArr(a) => a.data.into_iter().map(ext::of).collect_vec(),
+                    Ext(y) => vec![y],
+                    ExtArr(a) => a.data.into_vec(),
+                    _ => Vec::new(),
+                })
+                .collect_vec();
+            Ok(Array { data: data.into(), shape }.into())
+        } else if cells.iter().all(|c| matches!(c, Int(_) | IntArr(_) | Float(_) | FloatArr(_) | Ext(_) | ExtArr(_))) {
+            let data = cells.into_iter()
+                .flat_map(|c| match c {
+                    Int(y) => vec![y as f64],
+                    IntArr(a) => a.data.into_iter().map(|y| y as f64).collect_vec(),
+                    Float(y) => vec![y],
+                    FloatArr(a) => a.data.into_vec(),
+                    Ext(y) => vec![ext::float(&y)],
+                    ExtArr(a) => a.data.iter().map(ext::float).collect_vec(),
+                    _ => Vec::new(),
+                })
+                .collect_vec();
+            Ok(Array { data: data.into(), shape }.into())
+        } else if cells.iter().all(|c| matches!(c, Sym(_) | SymArr(_))) {
+            let data = cells.into_iter()
+                .flat_map(|c| match c {
+                    Sym(s) => vec![s],
+                    SymArr(a) => a.data.into_vec(),
+                    _ => Vec::new(),
+                })
+                .collect_vec();
+            Ok(SymArr(Array { data: data.into(), shape }))
+        } else if cells.iter().all(Val::is_text) {
+            let data = cells.into_iter()
+                .flat_map(|c| c.code_points().unwrap_or_default().data)
+                .collect_vec();
+            Ok(Val::chars(Array { data: data.into(), shape }))
+        } else if cells.iter().all(|c| matches!(c, ValArr(_))) {
+            let data = cells.into_iter()
+                .flat_map(|c| match c {
+                    ValArr(a) => a.data.into_vec(),
+                    _ => Vec::new(),
+                })
+                .collect_vec();
+            Ok(ValArr(Array { data: data.into(), shape }))
+        } else if cell_shape.is_empty() {
+            Ok(Array { data: cells.into(), shape }.into())
+        } else {
+            ALError::as_Domain("cannot lay out results of different types together")
+        }
+    }
+
+    fn rank_mon(u: Verb, r: i64, y: Val, env: &mut Env) -> Result<Val> {
+        let rank = y.shape_ref().len();
+        let r = cell_rank(r, rank);
+        if r == rank {
+            return eval_mon(u, y, env);
+        }
+        let frame = &y.shape_ref()[..rank - r];
+        if frame.contains(&0) {
+            return Ok(no_cells(frame.to_vec(), eval_mon(u, y.fill_cell(r), env)));
+        }
+        let (frame, cells) = y.split(r)?;
+        let res = cells.into_iter()
+            .map(|c| eval_mon(u.clone(), c, env))
+            .collect::<Result<Vec<_>>>()?;
+        Val::assemble(frame, res)
+    }
+
+    fn rank_dyd(u: Verb, l: i64, r: i64, x: Val, y: Val, env: &mut Env) -> Result<Val> {
+        let (xrank, yrank) = (x.shape_ref().len(), y.shape_ref().len());
+        let (l, r) = (cell_rank(l, xrank), cell_rank(r, yrank));
+        if l == xrank && r == yrank {
+            return eval_dyd(u, x, y, env);
+        }
+        let (xf, yf) = (&x.shape_ref()[..xrank - l], &y.shape_ref()[..yrank - r]);
+        // the shorter frame must be a prefix of the longer, its cells are repeated over the rest
+        let frame = if xf.len() > yf.len() { xf.to_vec() } else { yf.to_vec() };
+        if xf.iter().zip(yf).any(|(a, b)| a != b) {
+            return ALError::as_Length(format!("frames {xf:?} and {yf:?} dont agree"));
+        }
+        if frame.contains(&0) {
+            return Ok(no_cells(frame, eval_dyd(u, x.fill_cell(l), y.fill_cell(r), env)));
+        }
+        let (_, xs) = x.split(l)?;
+        let (_, ys) = y.split(r)?;
+        let n = xs.len().max(ys.len());
+        let (kx, ky) = (n / xs.len().max(1), n / ys.len().max(1));
+        let res = (0..n)
+            .map(|i| eval_dyd(u.clone(), xs[i / kx].clone(), ys[i / ky].clone(), env))
+            .collect::<Result<Vec<_>>>()?;
+        Val::assemble(frame, res)
+    }
+}
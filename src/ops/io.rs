@@ -2,24 +2,56 @@ use std::{env, fs, io::Read, os};
 
 use itertools::Itertools;
 
-use crate::{ALError, Array, Func, Val, Verb};
+use crate::{lexer, ALError, Array, Func, Val, Verb};
 
 
 pub trait IoOps {
     fn read(y: Val) -> Result<Val, ALError>; 
+    fn words(y: Val) -> Result<Val, ALError>;
+    fn encode(y: Val) -> Result<Val, ALError>;
+    fn decode(y: Val) -> Result<Val, ALError>;
 }
 
 impl IoOps for Val {
     fn read(y: Val) -> Result<Val, ALError> {
-        println!("cwd: {:?}", env::current_dir());
-        if let Val::AsciiArr(s) = y {
-            let mut buf = String::new();
-            let str: String = s.try_into()?;
-            fs::File::open(str)?.read_to_string(&mut buf)?;
-            Ok(buf.chars().collect_vec().into())
-        } else {
-            Err(ALError::Type(format!("val not stringable: {y:?}")))
-        }
+        let mut buf = String::new();
+        fs::File::open(String::try_from(y)?)?.read_to_string(&mut buf)?;
+        Ok(buf.chars().collect_vec().into())
+    }
+
+    /// `u. y`, the UTF-8 bytes of the text `y`.
+    fn encode(y: Val) -> Result<Val, ALError> {
+        let Some(a) = y.code_points() else {
+            return ALError::as_Domain("only text can be encoded");
+        };
+        let s = a.data.iter()
+            .map(|&c| char::from_u32(c).ok_or_else(|| ALError::Domain(format!("cannot encode the code point {c}"))))
+            .collect::<Result<String, _>>()?;
+        Ok(Array::from(s.bytes().map(i64::from).collect_vec()).into())
+    }
+
+    /// `u: y`, the text whose UTF-8 encoding is the bytes `y`.
+    fn decode(y: Val) -> Result<Val, ALError> {
+        let bytes = match y {
+            Val::Int(b) => vec![b],
+            Val::IntArr(a) if a.rank() == 1 => a.data.into_vec(),
+            y@(Val::Ascii(_) | Val::AsciiArr(_)) => y.code_points().unwrap_or_default().data.into_iter().map(i64::from).collect(),
+            y => return ALError::as_Domain(format!("cannot decode {y}")),
+        };
+        let bytes = bytes.into_iter()
+            .map(|b| u8::try_from(b).map_err(|_| ALError::Domain(format!("{b} is not a byte"))))
+            .collect::<Result<Vec<_>, _>>()?;
+        let s = String::from_utf8(bytes).map_err(|e| ALError::Domain(e.to_string()))?;
+        Ok(s.chars().collect_vec().into())
+    }
+
+    /// Splits the sentence `y` into its words, each in a box.
+    fn words(y: Val) -> Result<Val, ALError> {
+        let s = String::try_from(y)?;
+        let words: Vec<Val> = lexer::lex(&s).into_iter()
+            .map(|w| w.chars().collect_vec().into())
+            .collect_vec();
+        Ok(Array::from(words).into())
     }
     
 }
@@ -0,0 +1,344 @@
+use std::f64::consts::PI;
+
+use num::{BigRational, Integer, Signed, Zero};
+
+use crate::{ALError, Array, PrimVerb, Result, Val};
+use super::arith::{agree, ext, pervade};
+
+pub trait Scalar {
+    fn scalar_mon(p: PrimVerb, y: Val) -> Result<Val>;
+    fn scalar_dyd(p: PrimVerb, x: Val, y: Val) -> Result<Val>;
+}
+
+impl Scalar for Val {
+    fn scalar_mon(p: PrimVerb, y: Val) -> Result<Val> {
+        use PrimVerb::*;
+        let m = match p {
+            dash => NEGATE,
+            star => SIGNUM,
+            pcnt => RECIPROCAL,
+            larr_dot => FLOOR,
+            rarr_dot => CEILING,
+            hat => EXP,
+            hat_dot => LOG,
+            pcnt_col => SQRT,
+            bar => ABS,
+            dash_dot => NOT,
+            larr_col => DECREMENT,
+            rarr_col => INCREMENT,
+            o_dot => PI_TIMES,
+            p => return ALError::as_Nyi(format!("monadic {p}")),
+        };
+        m.apply(y)
+    }
+
+    fn scalar_dyd(p: PrimVerb, x: Val, y: Val) -> Result<Val> {
+        use PrimVerb::*;
+        let d = match p {
+            hat => POWER,
+            hat_dot => LOG_BASE,
+            pcnt_col => ROOT,
+            bar => RESIDUE,
+            plus_dot => GCD,
+            star_dot => LCM,
+            o_dot => CIRCLE,
+            p => return ALError::as_Nyi(format!("dyadic {p}")),
+        };
+        d.apply(x, y)
+    }
+}
+
+/// A scalar function in its integer, float and extended forms.
+/// The integer and extended forms give `None` where their result would not be exact,
+/// which moves the whole argument over to floats.
+#[derive(Clone, Copy)]
+struct Monad {
+    name: &'static str,
+    int: fn(i64) -> Option<i64>,
+    float: fn(f64) -> f64,
+    ext: fn(&BigRational) -> Option<BigRational>,
+    /// Whether integral float results are demoted back to integers, as for floor.
+    integral: bool,
+}
+
+#[derive(Clone, Copy)]
+struct Dyad {
+    name: &'static str,
+    int: fn(i64, i64) -> Option<i64>,
+    float: fn(f64, f64) -> f64,
+    /// Fails where an exact result would be too large to hold.
+    ext: fn(&BigRational, &BigRational) -> Result<Option<BigRational>>,
+}
+
+const NEGATE: Monad = Monad { name: "-", int: i64::checked_neg, float: |y| -y, ext: |y| Some(-y), integral: false };
+const SIGNUM: Monad = Monad {
+    name: "*",
+    int: |y| Some(y.signum()),
+    float: |y| if y == 0.0 { 0.0 } else { y.signum() },
+    ext: |y| Some(y.signum()),
+    integral: true,
+};
+const RECIPROCAL: Monad = Monad {
+    name: "%",
+    int: |_| None,
+    float: |y| 1.0 / y,
+    ext: |y| (!y.is_zero()).then(|| y.recip()),
+    integral: false,
+};
+const FLOOR: Monad = Monad { name: "<.", int: Some, float: f64::floor, ext: |y| Some(y.floor()), integral: true };
+const CEILING: Monad = Monad { name: ">.", int: Some, float: f64::ceil, ext: |y| Some(y.ceil()), integral: true };
+const EXP: Monad = Monad { name: "^", int: |_| None, float: f64::exp, ext: |_| None, integral: false };
+const LOG: Monad = Monad { name: "^.", int: |_| None, float: f64::ln, ext: |_| None, integral: false };
+const SQRT: Monad = Monad { name: "%:", int: |_| None, float: f64::sqrt, ext: |_| None, integral: false };
+const ABS: Monad = Monad { name: "|", int: i64::checked_abs, float: f64::abs, ext: |y| Some(y.abs()), integral: false };
+const NOT: Monad = Monad {
+    name: "-.",
+    int: |y| 1i64.checked_sub(y),
+    float: |y| 1.0 - y,
+    ext: |y| Some(ext::of(1) - y),
+    integral: false,
+};
+const DECREMENT: Monad = Monad {
+    name: "<:",
+    int: |y| y.checked_sub(1),
+    float: |y| y - 1.0,
+    ext: |y| Some(y - ext::of(1)),
+    integral: false,
+};
+const INCREMENT: Monad = Monad {
+    name: ">:",
+    int: |y| y.checked_add(1),
+    float: |y| y + 1.0,
+    ext: |y| Some(y + ext::of(1)),
+    integral: false,
+};
+const PI_TIMES: Monad = Monad { name: "o.", int: |_| None, float: |y| PI * y, ext: |_| None, integral: false };
+
+/// Most bits the numerator or denominator of an extended power may take.
+const MAX_BITS: u64 = 1 << 24;
+
+const POWER: Dyad = Dyad {
+    name: "^",
+    int: |x, y| x.checked_pow(u32::try_from(y).ok()?),
+    float: f64::powf,
+    ext: |x, y| {
+        let Some(n) = i32::try_from(y.to_integer()).ok().filter(|_| y.is_integer()) else {
+            return Ok(None);
+        };
+        let bits = x.numer().bits().max(x.denom().bits()).saturating_mul(n.unsigned_abs() as u64);
+        if bits > MAX_BITS {
+            return ALError::as_Limit(format!("{x} ^ {n} is too large"));
+        }
+        Ok((n >= 0 || !x.is_zero()).then(|| x.pow(n)))
+    },
+};
+const LOG_BASE: Dyad = Dyad { name: "^.", int: |_, _| None, float: |x, y| y.ln() / x.ln(), ext: |_, _| Ok(None) };
+const ROOT: Dyad = Dyad { name: "%:", int: |_, _| None, float: |x, y| y.powf(1.0 / x), ext: |_, _| Ok(None) };
+/// `x | y` is the remainder of `y` divided by `x`, taking the sign of `x`.
+const RESIDUE: Dyad = Dyad {
+    name: "|",
+    int: |x, y| match x {
+        0 => Some(y),
+        x => {
+            // only i64::MIN | -1 overflows, and its remainder is 0
+            let r = y.wrapping_rem(x);
+            Some(if r != 0 && (r < 0) != (x < 0) { r + x } else { r })
+        }
+    },
+    float: |x, y| if x == 0.0 || x.is_infinite() { y } else { y - x * (y / x).floor() },
+    ext: |x, y| Ok(Some(if x.is_zero() { y.clone() } else { y - x * (y / x).floor() })),
+};
+/// Greatest common divisor, which is or on booleans.
+const GCD: Dyad = Dyad {
+    name: "+.",
+    int: |x, y| i64::try_from(x.unsigned_abs().gcd(&y.unsigned_abs())).ok(),
+    float: gcd_float,
+    ext: |x, y| Ok(Some(gcd_ext(x, y))),
+};
+/// Least common multiple, which is and on booleans.
+const LCM: Dyad = Dyad {
+    name: "*.",
+    int: |x, y| match (x, y) {
+        (0, _) | (_, 0) => Some(0),
+        (x, y) => (x / x.gcd(&y)).checked_mul(y)?.checked_abs(),
+    },
+    float: |x, y| if x == 0.0 || y == 0.0 { 0.0 } else { (x * y / gcd_float(x, y)).abs() },
+    ext: |x, y| Ok(Some(if x.is_zero() || y.is_zero() { BigRational::zero() } else { (x * y / gcd_ext(x, y)).abs() })),
+};
+/// `x o. y` is the circle function `x` of `y`: 1 2 3 are sine, cosine and tangent,
+/// 5 6 7 their hyperbolic forms, 0 and 4 the complementary square roots,
+/// and negative `x` the inverse of `-x`.
+const CIRCLE: Dyad = Dyad {
+    name: "o.",
+    int: |_, _| None,
+    float: |x, y| match x {
+        0.0 => (1.0 - y * y).sqrt(),
+        1.0 => y.sin(),
+        2.0 => y.cos(),
+        3.0 => y.tan(),
+        4.0 => (1.0 + y * y).sqrt(),
+        5.0 => y.sinh(),
+        6.0 => y.cosh(),
+        7.0 => y.tanh(),
+        -1.0 => y.asin(),
+        -2.0 => y.acos(),
+        -3.0 => y.atan(),
+        -4.0 => (y * y - 1.0).sqrt(),
+        -5.0 => y.asinh(),
+        -6.0 => y.acosh(),
+        -7.0 => y.atanh(),
+        _ => f64::NAN,
+    },
+    ext: |_, _| Ok(None),
+};
+
+fn gcd_float(x: f64, y: f64) -> f64 {
+    if !(x.is_finite() && y.is_finite()) {
+        return f64::NAN;
+    }
+    let (mut a, mut b) = (x.abs(), y.abs());
+    while b != 0.0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn gcd_ext(x: &BigRational, y: &BigRational) -> BigRational {
+    let (p, q) = (x.numer() * y.denom(), y.numer() * x.denom());
+    BigRational::new(p.gcd(&q), x.denom() * y.denom())
+}
+
+/// Checks that a float result is real, NaN standing for a result outside the reals.
+fn real(name: &str, f: f64) -> Result<f64> {
+    if f.is_nan() {
+        ALError::as_Domain(format!("{name} has no real result here"))
+    } else {
+        Ok(f)
+    }
+}
+
+/// Integers where every float of `y` is integral, `y` unchanged otherwise.
+fn integral(y: Array<f64>) -> Val {
+    let int = |f: f64| (f.fract() == 0.0 && f.abs() < 9.2e18).then_some(f as i64);
+    match y.data.iter().map(|f| int(*f)).collect::<Option<Vec<_>>>() {
+        Some(data) => Val::from(Array { data: data.into(), shape: y.shape }).normal(),
+        None => Val::from(y).normal(),
+    }
+}
+
+/// `y` as floats, scalars as rank 0 arrays.
+pub(super) fn floats(y: Val) -> Result<Array<f64>> {
+    Ok(match y {
+        Val::Int(i) => Array { data: vec![i as f64].into(), shape: Vec::new() },
+        Val::Float(f) => Array { data: vec![f].into(), shape: Vec::new() },
+        Val::Ext(e) => Array { data: vec![ext::float(&e)].into(), shape: Vec::new() },
+        Val::IntArr(a) => a.into(),
+        Val::FloatArr(a) => a,
+        Val::ExtArr(Array { data, shape }) => Array { data: data.iter().map(ext::float).collect(), shape },
+        y => return ALError::as_Domain(format!("not a number: {y}")),
+    })
+}
+
+/// `y` as rationals, scalars as rank 0 arrays.
+pub(super) fn exts(y: Val) -> Result<Array<BigRational>> {
+    Ok(match y {
+        Val::Int(i) => Array { data: vec![ext::of(i)].into(), shape: Vec::new() },
+        Val::Ext(e) => Array { data: vec![e].into(), shape: Vec::new() },
+        Val::IntArr(Array { data, shape }) => Array { data: data.into_iter().map(ext::of).collect(), shape },
+        Val::ExtArr(a) => a,
+        y => return ALError::as_Domain(format!("not a rational: {y}")),
+    })
+}
+
+pub(super) fn ints(y: Val) -> Result<Array<i64>> {
+    match y {
+        Val::Int(i) => Ok(Array { data: vec![i].into(), shape: Vec::new() }),
+        Val::IntArr(a) => Ok(a),
+        y => ALError::as_Domain(format!("not an integer: {y}")),
+    }
+}
+
+impl Monad {
+    fn apply(self, y: Val) -> Result<Val> {
+        use Val::*;
+        match y {
+            Int(_) | IntArr(_) => {
+                let y = ints(y)?;
+                match y.data.iter().map(|i| (self.int)(*i)).collect::<Option<Vec<_>>>() {
+                    Some(data) => Ok(Val::from(Array { data: data.into(), shape: y.shape }).normal()),
+                    None => self.apply(FloatArr(y.into()).normal()),
+                }
+            },
+            Ext(e) => match (self.ext)(&e) {
+                Some(r) => Ok(Ext(r)),
+                None => self.apply(Float(ext::float(&e))),
+            },
+            ExtArr(a) => match a.data.iter().map(self.ext).collect::<Option<Vec<_>>>() {
+                Some(data) => Ok(ExtArr(Array { data: data.into(), shape: a.shape })),
+                None => self.apply(FloatArr(floats(ExtArr(a))?)),
+            },
+            Float(_) | FloatArr(_) => {
+                let Array { data, shape } = floats(y)?;
+                let data = data.into_iter().map(|f| real(self.name, (self.float)(f))).collect::<Result<Vec<_>>>()?;
+                let r = Array { data: data.into(), shape };
+                Ok(if self.integral { integral(r) } else { Val::from(r).normal() })
+            },
+            ValArr(Array { data, shape }) => {
+                let data = data.into_iter().map(|y| self.apply(y)).collect::<Result<Vec<_>>>()?;
+                Ok(ValArr(Array { data: data.into(), shape }))
+            },
+            Unit(y) => Ok(Unit(Box::new(self.apply(*y)?))),
+            y => ALError::as_Domain(format!("cannot apply {} to {y}", self.name)),
+        }
+    }
+}
+
+impl Dyad {
+    fn apply(self, x: Val, y: Val) -> Result<Val> {
+        use Val::*;
+        match (x, y) {
+            (x@(ValArr(_) | Unit(_)), y) | (x, y@(ValArr(_) | Unit(_))) => pervade(x, y, |x, y| self.apply(x, y)),
+            (x@(Int(_) | Ext(_)), y@(Int(_) | Ext(_))) if matches!(x, Ext(_)) || matches!(y, Ext(_)) => {
+                let rational = |v: &Val| match v {
+                    Ext(e) => Ok(e.clone()),
+                    Int(i) => Ok(ext::of(*i)),
+                    v => ALError::as_Domain(format!("not a rational: {v}")),
+                };
+                let (x, y) = (rational(&x)?, rational(&y)?);
+                match (self.ext)(&x, &y)? {
+                    Some(r) => Ok(Ext(r)),
+                    None => self.apply(Float(ext::float(&x)), Float(ext::float(&y))),
+                }
+            },
+            (x@(Ext(_) | ExtArr(_)), y@(IntArr(_) | FloatArr(_) | ExtArr(_))) |
+            (x@(IntArr(_) | FloatArr(_) | ExtArr(_)), y@(Ext(_) | ExtArr(_))) |
+            (x@ExtArr(_), y@(Int(_) | Float(_))) | (x@(Int(_) | Float(_)), y@ExtArr(_)) => {
+                pervade(x, y, |x, y| self.apply(x, y))
+            },
+            (x@(Int(_) | IntArr(_)), y@(Int(_) | IntArr(_))) => {
+                let (x, y) = (ints(x)?, ints(y)?);
+                let r = agree(&x, &y, self.int)?;
+                match r.data.into_iter().collect::<Option<Vec<_>>>() {
+                    Some(data) => Ok(Val::from(Array { data: data.into(), shape: r.shape }).normal()),
+                    None => self.apply(FloatArr(x.into()).normal(), FloatArr(y.into()).normal()),
+                }
+            },
+            (x, y) => {
+                let mut err = None;
+                // the first error stands for them all
+                let r = agree(&floats(x)?, &floats(y)?, |a, b| match real(self.name, (self.float)(a, b)) {
+                    Ok(f) => f,
+                    Err(e) => {
+                        err.get_or_insert(e);
+                        f64::NAN
+                    },
+                })?;
+                match err {
+                    Some(e) => Err(e),
+                    None => Ok(Val::from(r).normal()),
+                }
+            },
+        }
+    }
+}
+
@@ -3,12 +3,19 @@ mod shape;
 mod arith;
 mod adverb;
 mod io;
+mod cells;
+mod math;
+mod sort;
+mod search;
+mod sym;
 
 use std::any::TypeId;
 use itertools::Itertools;
 
 use crate::ops::io::IoOps;
-use crate::{is_arr, ALError, Adverb, Array, Func, PrimAdv, PrimConj, PrimVerb, Val, Verb};
+use crate::eval::eval_expl;
+use crate::{is_arr, ALError, Env, Adverb, Array, Func, PrimAdv, PrimConj, PrimVerb, Val, Verb};
+use crate::verb::rank_operand;
 use crate::PrimConj::*;
 use crate::PrimVerb::*;
 use crate::PrimAdv::*;
@@ -16,88 +23,170 @@ use crate::PrimAdv::*;
 use shape::*;
 use arith::*;
 use adverb::*;
+use cells::*;
+use math::*;
+use sort::*;
+use search::*;
+use sym::*;
 
-pub fn eval_mon(v: Verb,  y: Val) -> Result<Val, ALError> {
+pub fn eval_mon(v: Verb,  y: Val, env: &mut Env) -> Result<Val, ALError> {
     use Val::*;
     Ok(match v {
         Verb::Id(x) => *x,
-        Verb::Adv { u, p } => eval_mon_adv(*u, p, y)?,
+        Verb::Adv { u, p } => eval_mon_adv(*u, p, y, env)?,
         Verb::Conj { u, p, v } => match p {
-            at => eval_mon(*u, eval_mon(*v, y)?)?,
+            at => eval_mon(*u, eval_mon(*v, y, env)?, env)?,
             ampr_dot => { //before
-                let x = eval_mon(*u, y.clone());
-                eval_dyd( *v, x?, y)?
+                let x = eval_mon(*u, y.clone(), env);
+                eval_dyd( *v, x?, y, env)?
             }
             ampr_col => { //after
-                let x = eval_mon(*v, y.clone());
-                eval_dyd( *u, y, x?)?
+                let x = eval_mon(*v, y.clone(), env);
+                eval_dyd( *u, y, x?, env)?
             }
-            _ => panic!("todo: {p:?}"),
+            quot => {
+                let [m, _, _] = rank_operand(&v)?;
+                Val::rank_mon(*u, m, y, env)?
+            }
+            _ => return ALError::as_Nyi(format!("monadic {p}")),
         },
+        Verb::Expl { def, u, v } => eval_expl(&def, u, v, None, y, env)?,
         Verb::Fork { f, g, h } => {
             eval_dyd(*g ,
-                eval_mon(*f, y.clone())?,
-                eval_mon(*h, y.clone())?
+                eval_mon(*f, y.clone(), env)?,
+                eval_mon(*h, y.clone(), env)?,
+                env
             )?
         },
+        Verb::Prim(p) if splits(p.ranks()[0], &y) => Val::rank_mon(v, p.ranks()[0], y, env)?,
         Verb::Prim(p) => match p {
             i_dot => Val::read(y)?,
-            excl => Val::til_mon(y),
-            dllr => Val::shape_mon(y),
-            hash => Val::length_mon(y),
-            hash_col => Val::rank(&y),
+            excl => Val::til_mon(y)?,
+            dllr => Val::shape_mon(y)?,
+            hash => Val::length_mon(y)?,
+            hash_col => Val::rank(&y)?,
             lbrak | rbrak => y,
-            _ => panic!("todo: {p:?}"),
-        },
-        _ => panic!("todo: {v:?}"),
+            h_dot => y.first()?,
+            h_col | lcrl_dot => y.first_cell()?,
+            H_dot => y.last()?,
+            H_col => y.last_cell()?,
+            lcrl => y.catalogue()?,
+            rcrl_dot => Val::Int(1).drop(y)?,
+            i_col => Val::steps(y)?,
+            semi => y.raze()?,
+            semi_dot => y.group_indices()?,
+            semi_col => Val::words(y)?,
+            u_dot => Val::encode(y)?,
+            u_col => Val::decode(y)?,
+            s_col => Val::symbol(y)?,
+            s_dot => Val::name(y)?,
+            comma => y.ravel()?,
+            slsh_col => Val::grade(y, false)?,
+            tlde_dot => y.nub()?,
+            tlde_col => y.nub_sieve()?,
+            equal => y.classify()?,
+            hash_dot => y.occurrences()?,
+            larr => y.enclose(),
+            rarr => y.disclose()?,
+            bslsh_col => Val::grade(y, true)?,
+            p@(dash | star | pcnt | larr_dot | rarr_dot | hat | hat_dot | pcnt_col | bar | dash_dot
+                | larr_col | rarr_col | o_dot) => eval_arith_mon(p, y)?,
+            _ => return ALError::as_Nyi(format!("monadic {p}")),
+        }.normal(),
+        _ => return ALError::as_Nyi(format!("monadic {v}")),
     })
 }
 
 
 
-pub fn eval_dyd(v: Verb, x: Val, y: Val) -> Result<Val, ALError> {
+pub fn eval_dyd(v: Verb, x: Val, y: Val, env: &mut Env) -> Result<Val, ALError> {
    use Val::*;
     Ok(match v {
         Verb::Id(x) => *x,
+        Verb::Prim(p) if splits(p.ranks()[1], &x) || splits(p.ranks()[2], &y) => {
+            let [_, l, r] = p.ranks();
+            Val::rank_dyd(v, l, r, x, y, env)?
+        },
         Verb::Prim(p) => match p {
-            p@(plus | pcnt | star | dash | larr | rarr | equal) => eval_arith(p, x, y)?,
+            p@(plus | pcnt | star | dash | larr | rarr | equal | larr_dot | rarr_dot | larr_col | rarr_col
+                | hat | hat_dot | pcnt_col | bar | plus_dot | star_dot | o_dot) => eval_arith(p, x, y)?,
             lbrak => x,
             rbrak => y,
             semi_dot => x.group(y)?,
-            _ => panic!("nyi"),
-        },
+            h_dot => x.pick(y)?,
+            h_col => x.take(y)?,
+            // counting from the end: index 0 is the last, a count takes the last items
+            H_dot => (Int(-1) - x)?.pick(y)?,
+            H_col => (Int(0) - x)?.take(y)?,
+            lcrl => x.select(y)?,
+            lcrl_dot => x.take(y)?,
+            rcrl_dot => x.drop(y)?,
+            rcrl => x.amend(y)?,
+            dllr => Val::shape_dyd(x, y)?,
+            excl => Val::til_dyd(x, y)?,
+            hash => Val::length_dyd(x, y)?,
+            semi => x.link(y)?,
+            comma => x.append(y)?,
+            slsh_col => x.sort(y, false)?,
+            i_dot => x.index_of(y)?,
+            i_col => x.progressive_index(y)?,
+            e_dot => x.member_of(y)?,
+            E_dot => x.find(y)?,
+            bslsh_col => x.sort(y, true)?,
+            _ => return ALError::as_Nyi(format!("dyadic {p}")),
+        }.normal(),
         Verb::Conj { u, p, v } => match p {
-            at => eval_mon(*u, eval_dyd(*v, x, y)?)?,
+            at => eval_mon(*u, eval_dyd(*v, x, y, env)?, env)?,
             ampr_dot => { //before
-                let x = eval_mon(*u, x)?;
-                eval_dyd(*v, x, y)?
+                let x = eval_mon(*u, x, env)?;
+                eval_dyd(*v, x, y, env)?
             }
             ampr_col => { //after
-                let y = eval_mon(*v, y)?;
-                eval_dyd(*u, x, y)?
+                let y = eval_mon(*v, y, env)?;
+                eval_dyd(*u, x, y, env)?
             }
-            _ => panic!("todo"),
+            quot => {
+                let [_, l, r] = rank_operand(&v)?;
+                Val::rank_dyd(*u, l, r, x, y, env)?
+            }
+            _ => return ALError::as_Nyi(format!("dyadic {p}")),
         },
+        Verb::Expl { def, u, v } => eval_expl(&def, u, v, Some(x), y, env)?,
         Verb::Fork { f, g, h } => {
             eval_dyd(*g ,
-                eval_dyd(*f, x.clone(), y.clone())?,
-                eval_dyd(*h, x.clone(), y.clone())?
+                eval_dyd(*f, x.clone(), y.clone(), env)?,
+                eval_dyd(*h, x.clone(), y.clone(), env)?,
+                env
             )?
         },
-        _ => panic!("todo: {v:?}"),
+        _ => return ALError::as_Nyi(format!("dyadic {v}")),
     })
 }
 
+/// Whether a primitive of rank `r` has to be applied cell by cell to `y`.
+/// Atomic primitives take whole arrays and agree the frames themselves.
+fn splits(r: i64, y: &Val) -> bool {
+    r != 0 && cell_rank(r, y.shape_ref().len()) < y.shape_ref().len()
+}
+
+/// Whether `x v y` is arithmetic on numbers whose shapes agree, which always has a result.
+pub fn total(v: &Verb, x: &Val, y: &Val) -> bool {
+    use Val::*;
+    let number = |y: &Val| matches!(y, Int(_) | Float(_) | IntArr(_) | FloatArr(_));
+    matches!(v, Verb::Prim(plus | dash | star | pcnt | larr_dot | rarr_dot))
+        && number(x) && number(y) && shape_matches(x, y).is_none()
+}
+
 fn eval_arith(p: PrimVerb, x: Val, y: Val) -> Result<Val, ALError> {
     use Val::*;
-    if matches!(x, Sym(_) | ValFunc(_)) || matches!(y, Sym(_) | ValFunc(_)) {
-        let msg = format!("cannot eval: {x:?} {p:?} {y:?}");
-        Err(ALError::Type(msg))
+    if matches!(x, ValFunc(_)) || matches!(y, ValFunc(_)) {
+        let msg = format!("cannot apply {p} to {x} and {y}");
+        Err(ALError::Domain(msg))
     } else {
         if let Some(e) = shape_matches(&x, &y) {
             Err(e)
         } else {
-            Ok( match p {
+            match p {
                 plus => x + y,
                 pcnt => x / y,
                 star => x * y,
@@ -105,36 +194,680 @@ fn eval_arith(p: PrimVerb, x: Val, y: Val) -> Result<Val, ALError> {
                 larr => x.lt(y),
                 rarr => x.gt(y),
                 equal => x.eq(y),
-                p => unreachable!("not arith dyd verb: {p:?}"),
-            })
+                larr_dot => x.min(y),
+                rarr_dot => x.max(y),
+                larr_col => x.le(y),
+                rarr_col => x.ge(y),
+                hat | hat_dot | pcnt_col | bar | plus_dot | star_dot | o_dot => Val::scalar_dyd(p, x, y),
+                p => ALError::as_Domain(format!("{p} is not arithmetic")),
+            }
         }
     }
 }
 
+fn eval_arith_mon(p: PrimVerb, y: Val) -> Result<Val, ALError> {
+    if matches!(y, Val::Sym(_) | Val::ValFunc(_)) {
+        ALError::as_Domain(format!("cannot apply {p} to {y}"))
+    } else {
+        Val::scalar_mon(p, y)
+    }
+}
+
 fn shape_matches(x: &Val, y: &Val) -> Option<ALError> {
     let xs = x.shape_ref();
     let ys = y.shape_ref();
-    if xs.eq(ys) {
-        return None;
-    } else if xs.is_empty() || ys.is_empty() {
+    if xs.eq(ys) || xs.is_empty() || ys.is_empty() {
         return None;
     }
-    dbg!(xs, ys);
 
-    match x.shape_ref()
-        .iter()
-        .zip(y.shape_ref())
-        .find_position(|(x, y)| *x != *y) {
-        None | Some((0, (1, _))) | Some((0, (_, 1)))  => None,
-        Some(_) => Some(ALError::Shape("shape prefixes dont agree".to_string())),
+    // leading axis agreement: the shorter shape must be a prefix of the longer
+    if xs.starts_with(ys) || ys.starts_with(xs) {
+        None
+    } else {
+        Some(ALError::Length(format!("shapes {xs:?} and {ys:?} dont agree")))
     }
 }
 
-fn eval_mon_adv(u: Verb, a: PrimAdv, y:Val) -> Result<Val, ALError> {
+fn eval_mon_adv(u: Verb, a: PrimAdv, y:Val, env: &mut Env) -> Result<Val, ALError> {
     Ok(match a  {
-        slsh => Val::fold_mon(u, y)?,
-        bslsh => Val::scan(u, y)?,
-        _ => panic!("todo"),
+        slsh => Val::fold_mon(u, y, env)?,
+        bslsh => Val::scan(u, y, env)?,
     })
 }
 
+#[cfg(test)]
+mod tests {
+    use crate::eval::eval;
+    use crate::{ALError, Array, Env, PrimVerb, SpellInOut, Val, SPELL_IN_OUT};
+
+    fn run(src: &str) -> Result<Option<Val>, ALError> {
+        SPELL_IN_OUT.get_or_init(SpellInOut::init);
+        eval(src, &mut Env::default())
+    }
+
+    fn ints(data: Vec<i64>, shape: Vec<u32>) -> Option<Val> {
+        Some(Array { data: data.into(), shape }.into())
+    }
+
+    const NOUNS: [&str; 15] = [
+        "3", "_2", "0", "2.5", "1 2 3", "1.5 _2", "'abc'", "`sym", "(! 2 3)", "(! 0)", "(0 1 0 ;. 4 5 6)",
+        "9223372036854775807", "_9223372036854775807", "4000000000 4000000000", "1r2 3x",
+    ];
+
+    /// Every spelling of a primitive, whether or not it is registered.
+    fn words() -> Vec<String> {
+        (b'!'..=b'~')
+            .filter(|c| !matches!(c, b'\'' | b'(' | b')'))
+            .flat_map(|c| ["", ".", ":"].map(|i| format!("{}{i}", c as char)))
+            .collect()
+    }
+
+    /// Runs the sentences of `src` in one workspace, each of which must give `Ok` or `Err`.
+    fn no_panic(src: &str) {
+        let mut env = Env::default();
+        for sentence in src.split('\n') {
+            let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| eval(sentence, &mut env)));
+            assert!(r.is_ok(), "{sentence} panicked");
+        }
+    }
+
+    /// Numbers in every form the lexer reads, and some it should reject.
+    const LITERALS: [&str; 16] = [
+        "1r00", "1r_0", "1 2r00", "_", "__", "_.", "1e999", "_1e999", "2x", "1.5x", "1r2r3", "1.2.3",
+        "1e", "_1r_3", "99999999999999999999999", "0x",
+    ];
+
+    #[test]
+    fn test_fuzz_source() {
+        SPELL_IN_OUT.get_or_init(SpellInOut::init);
+        crate::with_stack(|| {
+            for n in LITERALS {
+                no_panic(n);
+                no_panic(&format!("1 {n} 2"));
+                no_panic(&format!("- {n}"));
+                no_panic(&format!("{n} + {n}"));
+                no_panic(&format!("{n} ^ 2x {n}"));
+                no_panic(&format!("f =: 3 : 'y + {n}'\nf {n}"));
+            }
+            no_panic("f =: 3 : 'f y'\nf 1");
+            no_panic("f =: 4 : 'x f y'\n1 f 2");
+            no_panic("f =: 3 : 'g y'\ng =: 3 : 'f y'\nf 1");
+            no_panic("f =: 3 : '1 + f y'\nf 1\nf 1");
+        });
+    }
+
+    #[test]
+    fn test_fuzz_verbs() {
+        SPELL_IN_OUT.get_or_init(SpellInOut::init);
+        for w in words() {
+            for y in NOUNS {
+                no_panic(&format!("{w} {y}"));
+                for x in NOUNS {
+                    no_panic(&format!("{x} {w} {y}"));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_fuzz_modifiers() {
+        SPELL_IN_OUT.get_or_init(SpellInOut::init);
+        for w in words() {
+            for y in NOUNS {
+                no_panic(&format!("+ {w} {y}"));
+                no_panic(&format!("1 2 + {w} {y}"));
+                no_panic(&format!("+ {w} - {y}"));
+                no_panic(&format!("{y} + {w} - {y}"));
+                no_panic(&format!("(+ {w} -) {y}"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let limit = |src| matches!(run(src), Err(ALError::At { err, .. }) if matches!(*err, ALError::Limit(_)));
+        assert!(limit("9223372036854775807 {. 1 2"));
+        assert!(limit("_9223372036854775807 {. 1 2"));
+        assert!(limit("4000000000 {. 1 2"));
+        assert!(limit("4000000000 4000000000 $ 1"));
+        assert!(limit("i: _9223372036854775807"));
+        assert!(limit("! 100000 100000"));
+        assert_eq!(run("_9223372036854775807 }. 1 2").unwrap(), ints(vec![], vec![0]));
+        assert_eq!(run("_5 {. 1 2").unwrap(), ints(vec![0, 0, 0, 1, 2], vec![5]));
+    }
+
+    #[test]
+    fn test_rank() {
+        assert_eq!(run("+/\"1 ! 2 3").unwrap(), ints(vec![3, 12], vec![2]));
+        assert_eq!(run("+/ ! 2 3").unwrap(), ints(vec![3, 5, 7], vec![3]));
+        assert_eq!(run("1 2 3 +\"1 ! 2 3").unwrap(), ints(vec![1, 3, 5, 4, 6, 8], vec![2, 3]));
+        assert_eq!(run("10 20 +\"0 1 ! 2 3").unwrap(), ints(vec![10, 11, 12, 23, 24, 25], vec![2, 3]));
+        assert_eq!(run("!\"0 (2 2)").unwrap(), ints(vec![0, 1, 0, 1], vec![2, 2]));
+        assert_eq!(run("+/\"_1 ! 2 3").unwrap(), ints(vec![3, 12], vec![2]));
+        assert_eq!(run("+/\"_ ! 2 3").unwrap(), ints(vec![3, 5, 7], vec![3]));
+        assert_eq!(run("-/ ! 4").unwrap(), Some(Val::Int(-2)));
+        assert!(matches!(run("1 2 +\"0 ! 3 2"), Err(ALError::At { err, .. }) if matches!(*err, ALError::Length(_))));
+        assert!(run("+\"1 2 3 4 ! 3").is_err());
+        let r = run("1 2 ;\"0 (3 4)").unwrap();
+        assert_eq!(r, Some(Val::ValArr(Array { data: vec![Val::Int(1), Val::Int(3), Val::Int(2), Val::Int(4)].into(), shape: vec![2, 2] })));
+        assert_eq!(run("h.\"1 (2 2 $ `a`b`c`d)").unwrap(), run("`a`c").unwrap());
+        assert_eq!(run("h.\"1 (2 2 $ 'abāc')").unwrap(), run("'aā'").unwrap());
+        // results of differing shapes are padded with fill, shorter ones taking leading unit axes
+        assert_eq!(run("!\"0 (1 2)").unwrap(), ints(vec![0, 0, 0, 1], vec![2, 2]));
+        assert_eq!(run("!\"0 (0 2 3)").unwrap(), ints(vec![0, 0, 0, 0, 1, 0, 0, 1, 2], vec![3, 3]));
+        assert_eq!(run(">\"0 (1 ; 7 8 ; 2 2 $ 5)").unwrap(), ints(vec![1, 0, 0, 0, 7, 8, 0, 0, 5, 5, 5, 5], vec![3, 2, 2]));
+        assert_eq!(run(">\"0 ('a' ; 'bc')").unwrap(), run("2 2 $ 'a bc'").unwrap());
+    }
+
+    #[test]
+    fn test_agreement() {
+        let r = run("(! 3 4) + 100 200 300").unwrap();
+        assert_eq!(r, ints((0..12).map(|i| i + 100 * (i / 4 + 1)).collect(), vec![3, 4]));
+        let r = run("(! 2 3) * ! 2 3 4").unwrap();
+        assert_eq!(r, ints((0..24).map(|i| i * (i / 4)).collect(), vec![2, 3, 4]));
+        let r = run("(! 2 3 4) - ! 2 3").unwrap();
+        assert_eq!(r, ints((0..24).map(|i| i - i / 4).collect(), vec![2, 3, 4]));
+        let r = run("1 2 < 3 0").unwrap();
+        assert_eq!(r, ints(vec![1, 0], vec![2]));
+        let r = run("2.5 * 2 4").unwrap();
+        assert_eq!(r, Some(Array { data: vec![5.0, 10.0].into(), shape: vec![2] }.into()));
+        let r = run("(! 3 4) + 1 2");
+        assert!(matches!(r, Err(ALError::At { err, .. }) if matches!(*err, ALError::Length(_))));
+        let r = run("(! 2 3) + ! 3 2");
+        assert!(matches!(r, Err(ALError::At { err, .. }) if matches!(*err, ALError::Length(_))));
+    }
+
+    #[test]
+    fn test_pervasion() {
+        let boxes = |a: Vec<Val>| Some(Val::ValArr(a.into()));
+        let r = run("(0 1 0 ;. 4 5 6) + 10 20").unwrap();
+        assert_eq!(r, boxes(vec![ints(vec![14, 16], vec![2]).unwrap(), ints(vec![25], vec![1]).unwrap()]));
+        let r = run("(0 1 0 ;. 4 5 6) * 0.5").unwrap();
+        let floats = |data: Vec<f64>| Val::FloatArr(Array { shape: vec![data.len() as u32], data: data.into() });
+        assert_eq!(r, boxes(vec![floats(vec![2.0, 3.0]), floats(vec![2.5])]));
+        let r = run("(0 1 0 ;. 4 5 6) < (0 1 0 ;. 5 5 5)").unwrap();
+        assert_eq!(r, boxes(vec![ints(vec![1, 0], vec![2]).unwrap(), ints(vec![0], vec![1]).unwrap()]));
+
+        let unit = Val::Unit(Box::new(ints(vec![1, 2], vec![2]).unwrap()));
+        let r = super::eval_arith(PrimVerb::plus, unit.clone(), Val::Int(1)).unwrap();
+        assert_eq!(r, Val::Unit(Box::new(ints(vec![2, 3], vec![2]).unwrap())));
+        let r = super::eval_arith(PrimVerb::star, unit, ints(vec![1, 2], vec![2]).unwrap()).unwrap();
+        assert_eq!(r, boxes(vec![ints(vec![1, 2], vec![2]).unwrap(), ints(vec![2, 4], vec![2]).unwrap()]).unwrap());
+
+        assert!(run("(0 1 0 ;. 4 5 6) + 1 2 3").is_err());
+        assert!(run("(0 1 0 ;. 4 5 6) + H: 0 1 0 ;. 4 5 6").is_err());
+    }
+
+    #[test]
+    fn test_scalar() {
+        let floats = |data: Vec<f64>| Some(Val::FloatArr(Array { shape: vec![data.len() as u32], data: data.into() }));
+        assert_eq!(run("- 1 _2").unwrap(), ints(vec![-1, 2], vec![2]));
+        assert_eq!(run("* _2.5 0 3").unwrap(), ints(vec![-1, 0, 1], vec![3]));
+        assert_eq!(run("<. 2.5 _2.5").unwrap(), ints(vec![2, -3], vec![2]));
+        assert_eq!(run(">. 2.5 _2.5").unwrap(), ints(vec![3, -2], vec![2]));
+        assert_eq!(run("| _3 4.5").unwrap(), floats(vec![3.0, 4.5]));
+        assert_eq!(run("-. 0 1").unwrap(), ints(vec![1, 0], vec![2]));
+        assert_eq!(run("<: >: 5").unwrap(), Some(Val::Int(5)));
+        assert_eq!(run("% 4").unwrap(), Some(Val::Float(0.25)));
+        assert_eq!(run("%: 16").unwrap(), Some(Val::Float(4.0)));
+        assert_eq!(run("2 ^ 10 _1").unwrap(), floats(vec![1024.0, 0.5]));
+        assert_eq!(run("2 ^ 10").unwrap(), Some(Val::Int(1024)));
+        assert_eq!(run("3 | _7 7 0").unwrap(), ints(vec![2, 1, 0], vec![3]));
+        assert_eq!(run("_3 | 7").unwrap(), Some(Val::Int(-2)));
+        assert_eq!(run("0 | 7").unwrap(), Some(Val::Int(7)));
+        assert_eq!(run("12 +. 18").unwrap(), Some(Val::Int(6)));
+        assert_eq!(run("4 *. 6").unwrap(), Some(Val::Int(12)));
+        assert_eq!(run("0 1 0 1 +. 0 0 1 1").unwrap(), ints(vec![0, 1, 1, 1], vec![4]));
+        assert_eq!(run("0 1 0 1 *. 0 0 1 1").unwrap(), ints(vec![0, 0, 0, 1], vec![4]));
+        assert_eq!(run("3 <. 1 5").unwrap(), ints(vec![1, 3], vec![2]));
+        assert_eq!(run("3 >. 1 5").unwrap(), ints(vec![3, 5], vec![2]));
+        assert_eq!(run("3 <: 2 3 4").unwrap(), ints(vec![0, 1, 1], vec![3]));
+        assert_eq!(run("3 >: 2 3 4").unwrap(), ints(vec![1, 1, 0], vec![3]));
+        assert_eq!(run("2 o. 0").unwrap(), Some(Val::Float(1.0)));
+        assert_eq!(run("_3 o. 1").unwrap(), Some(Val::Float(std::f64::consts::FRAC_PI_4)));
+        assert_eq!(run("o. 1").unwrap(), Some(Val::Float(std::f64::consts::PI)));
+        assert_eq!(run("2x ^ 70").unwrap(), Some(Val::Ext("1180591620717411303424".parse().unwrap())));
+        assert!(matches!(run("^. _1"), Err(ALError::At { err, .. }) if matches!(*err, ALError::Domain(_))));
+        assert!(run("9 o. 1").is_err());
+    }
+
+    fn boxes(data: Vec<Val>) -> Option<Val> {
+        Some(Val::ValArr(data.into()))
+    }
+
+    #[test]
+    fn test_head_tail() {
+        assert_eq!(run("h. ! 2 3").unwrap(), Some(Val::Int(0)));
+        assert_eq!(run("H. ! 2 3").unwrap(), Some(Val::Int(5)));
+        assert_eq!(run("h: ! 2 3").unwrap(), ints(vec![0, 1, 2], vec![3]));
+        assert_eq!(run("H: ! 2 3").unwrap(), ints(vec![3, 4, 5], vec![3]));
+        assert_eq!(run("{. ! 2 3").unwrap(), ints(vec![0, 1, 2], vec![3]));
+        assert_eq!(run("{. 7 8").unwrap(), Some(Val::Unit(Box::new(Val::Int(7)))));
+        assert_eq!(run("h. 5").unwrap(), Some(Val::Int(5)));
+        assert!(run("h. ! 0").is_err());
+        assert_eq!(run("h: 3 0 $ 0").unwrap(), ints(vec![], vec![0]));
+        assert_eq!(run("H: 3 0 $ 0").unwrap(), ints(vec![], vec![0]));
+        assert_eq!(run("H. 3 0 $ 0").unwrap(), Some(Val::Int(0)));
+        assert_eq!(run("2 h: 1 2 3").unwrap(), ints(vec![1, 2], vec![2]));
+        assert_eq!(run("2 H: 1 2 3").unwrap(), ints(vec![2, 3], vec![2]));
+        assert_eq!(run("0 H. 1 2 3").unwrap(), Some(Val::Int(3)));
+        assert_eq!(run("0 1 H. ! 2 3").unwrap(), Some(Val::Int(4)));
+    }
+
+    #[test]
+    fn test_take_drop() {
+        assert_eq!(run("2 {. 4 5 6").unwrap(), ints(vec![4, 5], vec![2]));
+        assert_eq!(run("_5 {. 4 5 6").unwrap(), ints(vec![0, 0, 4, 5, 6], vec![5]));
+        assert_eq!(run("2 {. 9").unwrap(), ints(vec![9, 0], vec![2]));
+        assert_eq!(run("1 {. ! 3 2").unwrap(), ints(vec![0, 1], vec![1, 2]));
+        assert_eq!(run("1 }. ! 3 2").unwrap(), ints(vec![2, 3, 4, 5], vec![2, 2]));
+        assert_eq!(run("_1 }. 4 5 6").unwrap(), ints(vec![4, 5], vec![2]));
+        assert_eq!(run("}. 4 5 6").unwrap(), ints(vec![5, 6], vec![2]));
+        assert_eq!(run("9 }. 4 5 6").unwrap(), ints(vec![], vec![0]));
+    }
+
+    #[test]
+    fn test_select_pick() {
+        assert_eq!(run("2 0 { 4 5 6").unwrap(), ints(vec![6, 4], vec![2]));
+        assert_eq!(run("_1 { ! 3 2").unwrap(), ints(vec![4, 5], vec![2]));
+        assert_eq!(run("(! 2 2) { 4 5 6 7").unwrap(), ints(vec![4, 5, 6, 7], vec![2, 2]));
+        assert_eq!(run("1 { 4 5 6").unwrap(), Some(Val::Unit(Box::new(Val::Int(5)))));
+        assert_eq!(run("1 2 h. ! 3 4").unwrap(), Some(Val::Int(6)));
+        assert_eq!(run("1 h. 1 ; 2").unwrap(), Some(Val::Int(2)));
+        assert!(matches!(run("3 { 4 5 6"), Err(ALError::At { err, .. }) if matches!(*err, ALError::Index(_))));
+        assert!(matches!(run("1 h. ! 3 4"), Err(ALError::At { err, .. }) if matches!(*err, ALError::Rank(_))));
+    }
+
+    #[test]
+    fn test_raze_link() {
+        assert_eq!(run("1 ; 2 3").unwrap(), boxes(vec![Val::Int(1), ints(vec![2, 3], vec![2]).unwrap()]));
+        assert_eq!(run("1 ; 2 ; 3").unwrap(), boxes(vec![Val::Int(1), Val::Int(2), Val::Int(3)]));
+        assert_eq!(run("; 1 2 ; 3 ; 4 5").unwrap(), ints(vec![1, 2, 3, 4, 5], vec![5]));
+        assert_eq!(run("; (! 2 2) ; 9 9").unwrap(), ints(vec![0, 1, 2, 3, 9, 9], vec![3, 2]));
+        assert_eq!(run("; ! 2 2").unwrap(), ints(vec![0, 1, 2, 3], vec![4]));
+        assert!(run("; (! 2 2) ; 1 2 3").is_err());
+    }
+
+    #[test]
+    fn test_join() {
+        assert_eq!(run(", ! 2 3").unwrap(), ints((0..6).collect(), vec![6]));
+        assert_eq!(run(", 5").unwrap(), ints(vec![5], vec![1]));
+        assert_eq!(run("1 2 , 3").unwrap(), ints(vec![1, 2, 3], vec![3]));
+        assert_eq!(run("(! 2 3) , 7 8 9").unwrap(), ints(vec![0, 1, 2, 3, 4, 5, 7, 8, 9], vec![3, 3]));
+        assert_eq!(run("(! 2 2) , 7").unwrap(), ints(vec![0, 1, 2, 3, 7, 7], vec![3, 2]));
+        assert_eq!(run("1 , 2.5").unwrap(), Some(Array { data: vec![1.0, 2.5].into(), shape: vec![2] }.into()));
+        assert_eq!(run("'ab' , 'cd'").unwrap(), Some(Val::from("abcd".chars().collect::<Vec<_>>())));
+        assert!(matches!(run("(! 2 3) , 1 2"), Err(ALError::At { err, .. }) if matches!(*err, ALError::Length(_))));
+        assert!(matches!(run("'abc' , 1"), Err(ALError::At { err, .. }) if matches!(*err, ALError::Domain(_))));
+        assert!(matches!(run("`a`b , 'x'"), Err(ALError::At { err, .. }) if matches!(*err, ALError::Domain(_))));
+    }
+
+    #[test]
+    fn test_amend_catalogue() {
+        assert_eq!(run("(9 ; 0 2) } 1 2 3").unwrap(), ints(vec![9, 2, 9], vec![3]));
+        assert_eq!(run("(7 8 ; _1) } ! 3 2").unwrap(), ints(vec![0, 1, 2, 3, 7, 8], vec![3, 2]));
+        assert_eq!(run("(7 8 ; 0 2) } 1 2 3").unwrap(), ints(vec![7, 2, 8], vec![3]));
+        assert!(run("(7 8 ; 0) } 1 2 3").is_err());
+        assert!(run("(7 ; 3) } 1 2 3").is_err());
+        assert!(matches!(run("(9 ; 0) } 'abc'"), Err(ALError::At { err, .. }) if matches!(*err, ALError::Domain(_))));
+        let pair = |a, b| ints(vec![a, b], vec![2]).unwrap();
+        let r = run("{ 1 2 ; 3 4").unwrap();
+        assert_eq!(r, Some(Val::ValArr(Array { data: vec![pair(1, 3), pair(1, 4), pair(2, 3), pair(2, 4)].into(), shape: vec![2, 2] })));
+    }
+
+    #[test]
+    fn test_steps_words() {
+        assert_eq!(run("i: 2").unwrap(), ints(vec![-2, -1, 0, 1, 2], vec![5]));
+        assert_eq!(run("i: _1").unwrap(), ints(vec![1, 0, -1], vec![3]));
+        let word = |w: &str| Val::from(w.chars().collect::<Vec<_>>());
+        let r = run(";: 'a =. 1 2 + b'").unwrap();
+        assert_eq!(r, boxes(vec![word("a"), word("=."), word("1"), word("2"), word("+"), word("b")]));
+    }
+
+    #[test]
+    fn test_reshape() {
+        assert_eq!(run("2 3 $ 1 2").unwrap(), ints(vec![1, 2, 1, 2, 1, 2], vec![2, 3]));
+        assert_eq!(run("2 _ $ ! 6").unwrap(), ints((0..6).collect(), vec![2, 3]));
+        assert_eq!(run("_ 2 $ ! 2 2").unwrap(), ints(vec![0, 1, 2, 3], vec![2, 2]));
+        assert_eq!(run("3 $ 1.5").unwrap(), Some(Array { data: vec![1.5; 3].into(), shape: vec![3] }.into()));
+        assert_eq!(run("5 $ 'ab'").unwrap(), Some(Val::from("ababa".chars().collect::<Vec<_>>())));
+        assert_eq!(run("3 $ 1 ; 2").unwrap(), boxes(vec![Val::Int(1), Val::Int(2), Val::Int(1)]));
+        assert_eq!(run("(! 0) $ 7 8").unwrap(), Some(Val::Int(7)));
+        assert_eq!(run("0 $ ! 0").unwrap(), ints(vec![], vec![0]));
+        let err = |src: &str| match run(src) {
+            Err(ALError::At { err, .. }) => *err,
+            r => panic!("{src} gave {r:?}"),
+        };
+        assert!(matches!(err("2 2 $ ! 0"), ALError::Length(_)));
+        assert!(matches!(err("2 _ $ ! 5"), ALError::Length(_)));
+        assert!(matches!(err("_ _ $ 1"), ALError::Domain(_)));
+        assert!(matches!(err("_1 $ 1"), ALError::Domain(_)));
+        assert_eq!(err("_1 $ 1 2").to_string(), ALError::Domain("bad shape -1".into()).to_string());
+        assert!(matches!(err("_ 9223372036854775807 $ 1"), ALError::Domain(_)));
+        assert!(matches!(err("2 1.5 $ 1"), ALError::Domain(_)));
+    }
+
+    #[test]
+    fn test_grade_sort() {
+        assert_eq!(run("/: 3 1 2 1").unwrap(), ints(vec![1, 3, 2, 0], vec![4]));
+        assert_eq!(run("\\: 3 1 2 1").unwrap(), ints(vec![0, 2, 1, 3], vec![4]));
+        assert_eq!(run("/: 2.5 1 3").unwrap(), ints(vec![1, 0, 2], vec![3]));
+        assert_eq!(run("/: 9223372036854775807 9.3e18").unwrap(), ints(vec![0, 1], vec![2]));
+        assert_eq!(run("/: 9007199254740993 ; 9007199254740992.0 ; 9007199254740992").unwrap(), ints(vec![1, 2, 0], vec![3]));
+        assert_eq!(run("/: 9223372036854775807 ; 9223372036854775807.0 ; _9223372036854775807 ; _9.3e18").unwrap(), ints(vec![3, 2, 0, 1], vec![4]));
+        assert_eq!(run("'banana' /: 'banana'").unwrap(), Some(Val::from("aaabnn".chars().collect::<Vec<_>>())));
+        let t = "(3 2 $ 3 1 1 2 1 1)";
+        assert_eq!(run(&format!("{t} /: {t}")).unwrap(), ints(vec![1, 1, 1, 2, 3, 1], vec![3, 2]));
+        assert_eq!(run(&format!("{t} /: 0 {{\"1 {t}")).unwrap(), ints(vec![1, 2, 1, 1, 3, 1], vec![3, 2]));
+        assert_eq!(run(&format!("{t} \\: 1 {{\"1 {t}")).unwrap(), ints(vec![1, 2, 3, 1, 1, 1], vec![3, 2]));
+        assert_eq!(run("/: `b ; `a ; 1").unwrap(), ints(vec![2, 1, 0], vec![3]));
+        assert_eq!(run("/: 1 2 ; 1 ; 1 2 3 ; 'ab'").unwrap(), ints(vec![1, 0, 2, 3], vec![4]));
+        assert!(matches!(run("1 2 /: 1 2 3"), Err(ALError::At { err, .. }) if matches!(*err, ALError::Length(_))));
+        assert!(run("/: 5").is_err());
+    }
+
+    #[test]
+    fn test_search() {
+        assert_eq!(run("3 1 4 1 5 i. 1 5 9").unwrap(), ints(vec![1, 4, 5], vec![3]));
+        assert_eq!(run("'hello' i. 'lo'").unwrap(), ints(vec![2, 4], vec![2]));
+        assert_eq!(run("(3 2 $ ! 6) i. 2 3").unwrap(), Some(Val::Int(1)));
+        assert_eq!(run("(3 2 $ ! 6) i. ! 2 2").unwrap(), ints(vec![0, 1], vec![2]));
+        assert_eq!(run("(3 2 $ ! 6) i. 1 2 3").unwrap(), Some(Val::Int(3)));
+        assert_eq!(run("1.5 2.5 i. 2.5 1.5000000000000002 3").unwrap(), ints(vec![1, 0, 2], vec![3]));
+        assert_eq!(run("1 2 3 i. 2.0000000000000004").unwrap(), Some(Val::Int(1)));
+        assert_eq!(run("(1 2 ; 'ab') i. 'ab' ; 3").unwrap(), ints(vec![1, 2], vec![2]));
+        assert_eq!(run("1 2 3 e. 2 5").unwrap(), ints(vec![0, 1, 0], vec![3]));
+        assert_eq!(run("'abc' e. 'banana'").unwrap(), ints(vec![1, 1, 0], vec![3]));
+        assert_eq!(run("1 1 2 1 i: 1 1 1 1 2").unwrap(), ints(vec![0, 1, 3, 4, 2], vec![5]));
+        assert_eq!(run("1.0 1 2 i: 1 1 1").unwrap(), ints(vec![0, 1, 3], vec![3]));
+        assert_eq!(run("'an' E. 'banana'").unwrap(), ints(vec![0, 1, 0, 1, 0, 0], vec![6]));
+        assert_eq!(run("(1 1 $ 5) E. 2 2 $ 5 1 5 5").unwrap(), ints(vec![1, 0, 1, 1], vec![2, 2]));
+        assert_eq!(run("(2 2 $ 5) E. 2 3 $ 5 5 1 5 5 5").unwrap(), ints(vec![1, 0, 0, 0, 0, 0], vec![2, 3]));
+        let n = 200_000;
+        assert_eq!(run(&format!("(! {n}) i. ! {n}")).unwrap(), ints((0..n).collect(), vec![n as u32]));
+        let r = run(&format!("(% 1 + ! {n}) i. % 1 + ! {n}")).unwrap();
+        assert_eq!(r, ints((0..n).collect(), vec![n as u32]));
+    }
+
+    #[test]
+    fn test_nub() {
+        assert_eq!(run("~. 3 1 3 2 1").unwrap(), ints(vec![3, 1, 2], vec![3]));
+        assert_eq!(run("~: 3 1 3 2 1").unwrap(), ints(vec![1, 1, 0, 1, 0], vec![5]));
+        assert_eq!(run("= 3 1 3 2 1").unwrap(), ints(vec![0, 1, 0, 2, 1], vec![5]));
+        assert_eq!(run("#. 3 1 3 2 1").unwrap(), ints(vec![0, 0, 1, 0, 1], vec![5]));
+        assert_eq!(run("~. 3 2 $ 1 2 3 4 1 2").unwrap(), ints(vec![1, 2, 3, 4], vec![2, 2]));
+        assert_eq!(run("= 3 2 $ 1 2 3 4 1 2").unwrap(), ints(vec![0, 1, 0], vec![3]));
+        assert_eq!(run("~. 'hello'").unwrap(), Some(Val::from("helo".chars().collect::<Vec<_>>())));
+        assert_eq!(run("~. 1 ; 2 ; 1").unwrap(), boxes(vec![Val::Int(1), Val::Int(2)]));
+        assert_eq!(run("= 1.0 1 2.0000000000000004 2").unwrap(), ints(vec![0, 0, 1, 1], vec![4]));
+        assert_eq!(run("~. 5").unwrap(), ints(vec![5], vec![1]));
+        assert_eq!(run("~: 0 $ 0").unwrap(), ints(vec![], vec![0]));
+    }
+
+    #[test]
+    fn test_group() {
+        let list = |data: Vec<i64>| Val::from(Array { shape: vec![data.len() as u32], data: data.into() });
+        assert_eq!(run("0 1 0 _1 2 ;. 5 6 7 8 9").unwrap(), boxes(vec![list(vec![5, 7]), list(vec![6]), list(vec![9])]));
+        assert_eq!(run("0 0 4 ;. 5 6").unwrap(), boxes(vec![list(vec![5, 6]), list(vec![]), list(vec![]), list(vec![])]));
+        assert_eq!(run(";. 1 0 1").unwrap(), boxes(vec![list(vec![1]), list(vec![0, 2])]));
+        let r = run("1 0 ;. ! 2 2").unwrap();
+        assert_eq!(r, boxes(vec![ints(vec![2, 3], vec![1, 2]).unwrap(), ints(vec![0, 1], vec![1, 2]).unwrap()]));
+        let r = run("(2 2 $ 0 1 1 0) ;. 2 2 $ 5 6 7 8").unwrap();
+        assert_eq!(r, boxes(vec![list(vec![5, 8]), list(vec![6, 7])]));
+        let r = run("(0 1 0 ; 1 0) ;. ! 3 2").unwrap();
+        let cells = vec![
+            ints(vec![1, 5], vec![2, 1]).unwrap(), ints(vec![0, 4], vec![2, 1]).unwrap(),
+            ints(vec![3], vec![1, 1]).unwrap(), ints(vec![2], vec![1, 1]).unwrap(),
+        ];
+        assert_eq!(r, Some(Val::ValArr(Array { data: cells.into(), shape: vec![2, 2] })));
+        assert_eq!(run("0 0 1 ;. 'abc'").unwrap(), Some(Val::ValArr(vec![Val::from(vec!['a', 'b']), Val::from(vec!['c'])].into())));
+        assert!(matches!(run("0 1 ;. 1 2 3"), Err(ALError::At { err, .. }) if matches!(*err, ALError::Length(_))));
+        assert!(matches!(run("_2 0 ;. 1 2"), Err(ALError::At { err, .. }) if matches!(*err, ALError::Domain(_))));
+        assert!(matches!(run("0.5 0 ;. 1 2"), Err(ALError::At { err, .. }) if matches!(*err, ALError::Domain(_))));
+    }
+
+    #[test]
+    fn test_chars() {
+        let text = |s: &str| Some(Val::from(s.chars().collect::<Vec<_>>()));
+        assert_eq!(run("h. 'abc'").unwrap(), Some(Val::Ascii(b'a')));
+        assert_eq!(run("2 h. 'abc'").unwrap(), Some(Val::Ascii(b'c')));
+        assert_eq!(run("1 { 'abc'").unwrap(), Some(Val::Unit(Box::new(Val::Ascii(b'b')))));
+        assert_eq!(run("2 0 { 'abc'").unwrap(), text("ca"));
+        assert_eq!(run("'abc' + 1").unwrap(), text("bcd"));
+        assert_eq!(run("'a' + 2").unwrap(), Some(Val::Ascii(b'c')));
+        assert_eq!(run("'bcd' - 'abc'").unwrap(), ints(vec![1, 1, 1], vec![3]));
+        assert_eq!(run("'abc' - 32").unwrap(), text("ABC"));
+        assert_eq!(run("'a' + 1000").unwrap(), Some(Val::Utf16(0x449)));
+        assert_eq!(run("'abc' = 'abd'").unwrap(), ints(vec![1, 1, 0], vec![3]));
+        assert_eq!(run("'ab' > 99 1.5").unwrap(), ints(vec![1, 1], vec![2]));
+        assert_eq!(run("'ab' >. 'ba'").unwrap(), text("bb"));
+        assert_eq!(run("'ab' , h. 'x'").unwrap(), text("abx"));
+        let domain = |s: &str| matches!(run(s), Err(ALError::At { err, .. }) if matches!(*err, ALError::Domain(_)));
+        assert!(domain("1 - 'abc'"));
+        assert!(domain("'abc' * 2"));
+        assert!(domain("'a' + 'b'"));
+        assert!(domain("- 'a'"));
+        assert!(domain("'a' - 98"));
+    }
+
+    #[test]
+    fn test_unicode() {
+        let text = |s: &str| Some(Val::from(s.chars().collect::<Vec<_>>()));
+        assert!(matches!(run("'héllo'").unwrap(), Some(Val::AsciiArr(_))));
+        assert!(matches!(run("'aↄ'").unwrap(), Some(Val::Utf16Arr(_))));
+        assert_eq!(run("'ab' , 'ↄd'").unwrap(), text("abↄd"));
+        assert_eq!(run("'😀' , 'é'").unwrap(), text("😀é"));
+        assert_eq!(run("(h. 'é') , 'ↄ'").unwrap(), text("éↄ"));
+        assert_eq!(run("'ↄé' i. 'é'").unwrap(), Some(Val::Int(1)));
+        assert_eq!(run("u. 'aé'").unwrap(), ints(vec![97, 195, 169], vec![3]));
+        assert_eq!(run("u: 240 159 152 128").unwrap(), text("😀"));
+        assert_eq!(run("u: u. 'aↄ😀'").unwrap(), text("aↄ😀"));
+        assert!(matches!(run("u: 255"), Err(ALError::At { err, .. }) if matches!(*err, ALError::Domain(_))));
+
+        colored::control::set_override(false);
+        let show = |s: &str| run(s).unwrap().unwrap().to_string();
+        assert_eq!(show("'héllo'"), "\"héllo\"");
+        assert_eq!(show("'aↄ\n'"), "\"aↄ\\n\"");
+        assert_eq!(show("h. 'é'"), "'é'");
+        assert_eq!(show("2 2 $ 'abↄd'"), "[\"ab\" \"ↄd\"]");
+    }
+
+    #[test]
+    fn test_symbols() {
+        use crate::intern;
+        let syms = |names: &[&str]| Some(Val::SymArr(names.iter().map(|n| intern(n)).collect::<Vec<_>>().into()));
+        assert_eq!(run("`a`b`c").unwrap(), syms(&["a", "b", "c"]));
+        assert_eq!(run("`abc").unwrap(), Some(Val::Sym(intern("abc"))));
+        assert_eq!(run("`a`b`c = `a`x`c").unwrap(), ints(vec![1, 0, 1], vec![3]));
+        assert_eq!(run("`b < `a`c").unwrap(), ints(vec![0, 1], vec![2]));
+        assert_eq!(run("`a`b`c < 1").unwrap(), ints(vec![0, 0, 0], vec![3]));
+        assert_eq!(run("`a`b`c i. `c`q").unwrap(), ints(vec![2, 3], vec![2]));
+        assert_eq!(run("/: `c`a`b").unwrap(), ints(vec![1, 2, 0], vec![3]));
+        assert_eq!(run("~. `a`b`a").unwrap(), syms(&["a", "b"]));
+        assert_eq!(run("`a`b , `c").unwrap(), syms(&["a", "b", "c"]));
+        assert_eq!(run("h. `a`b").unwrap(), Some(Val::Sym(intern("a"))));
+        assert_eq!(run("3 {. `a`b").unwrap(), syms(&["a", "b", ""]));
+        assert_eq!(run("s: 'abc'").unwrap(), Some(Val::Sym(intern("abc"))));
+        assert_eq!(run("s: 'ab' ; 'c'").unwrap(), syms(&["ab", "c"]));
+        assert_eq!(run("s. `ab`c").unwrap(), boxes(vec![Val::from(vec!['a', 'b']), Val::from(vec!['c'])]));
+        assert!(matches!(run("`a + 1"), Err(ALError::At { err, .. }) if matches!(*err, ALError::Domain(_))));
+        assert_eq!(run("1 < `a").unwrap(), Some(Val::Int(1)));
+        assert_eq!(run("`a < 'a'").unwrap(), Some(Val::Int(0)));
+        assert_eq!(run("'ab' < `a").unwrap(), ints(vec![1, 1], vec![2]));
+        assert!(matches!(run("'a' + `a"), Err(ALError::At { err, .. }) if matches!(*err, ALError::Domain(_))));
+
+        colored::control::set_override(false);
+        assert_eq!(run("`a`bc").unwrap().unwrap().to_string(), "[`a `bc]");
+    }
+
+    #[test]
+    fn test_lists() {
+        let list = |v: Vec<i64>| Val::from(Array::from(v));
+        assert_eq!(run("⟨1, 2 3⟩").unwrap(), boxes(vec![Val::Int(1), list(vec![2, 3])]));
+        assert_eq!(run("1‿2‿3").unwrap(), boxes(vec![Val::Int(1), Val::Int(2), Val::Int(3)]));
+        assert_eq!(run("⟨(1 , 2) ⋄ 3⟩‿4").unwrap(), boxes(vec![boxes(vec![list(vec![1, 2]), Val::Int(3)]).unwrap(), Val::Int(4)]));
+        assert_eq!(run("# ⟨⟩").unwrap(), Some(Val::Int(0)));
+        assert_eq!(run("> 1‿2‿3").unwrap(), ints(vec![1, 2, 3], vec![3]));
+        assert_eq!(run("> ⟨1 2, 3 4⟩").unwrap(), ints(vec![1, 2, 3, 4], vec![2, 2]));
+        assert_eq!(run("< 1 2").unwrap(), Some(Val::Unit(Box::new(list(vec![1, 2])))));
+        assert_eq!(run("> < 1 2").unwrap(), ints(vec![1, 2], vec![2]));
+        assert!(matches!(run("⟨1,,2⟩"), Err(ALError::At { err, .. }) if matches!(*err, ALError::Syntax(_))));
+        assert!(matches!(run("‿1"), Err(ALError::At { err, .. }) if matches!(*err, ALError::Syntax(_))));
+        let syntax = |src: &str| match run(src) {
+            Err(ALError::At { span, err, .. }) if matches!(*err, ALError::Syntax(_)) => src[span.start..span.end].to_string(),
+            r => panic!("{src} gave {r:?}"),
+        };
+        assert_eq!(syntax("1 + ⟨2, 3"), "⟨");
+        assert_eq!(syntax("1 2⟩"), "⟩");
+        assert_eq!(syntax("1‿"), "‿");
+        assert_eq!(syntax("(1‿) + 2"), "‿");
+    }
+
+    #[test]
+    fn test_fill() {
+        let text = |s: &str| Some(Val::from(s.chars().collect::<Vec<_>>()));
+        assert_eq!(run("5 {. 'ab'").unwrap(), text("ab   "));
+        assert_eq!(run("_3 {. 1.5").unwrap(), Some(Array { data: vec![0.0, 0.0, 1.5].into(), shape: vec![3] }.into()));
+        assert_eq!(run("3 {. 'ab' ; 'cd'").unwrap(), boxes(vec![text("ab").unwrap(), text("cd").unwrap(), text("").unwrap()]));
+        assert_eq!(run("0 3 $ 'a'").unwrap(), Some(Val::AsciiArr(Array { data: vec![].into(), shape: vec![0, 3] })));
+        assert_eq!(run("$ #\"1 (0 4 $ 0)").unwrap(), ints(vec![0], vec![1]));
+        assert_eq!(run("(0 2 $ 'c') ,\"1 'ab'").unwrap(), Some(Val::AsciiArr(Array { data: vec![].into(), shape: vec![0, 4] })));
+        assert_eq!(run("2 0 $ 1.5").unwrap(), Some(Val::FloatArr(Array { data: vec![].into(), shape: vec![2, 0] })));
+        assert_eq!(run("(! 0) , ''").unwrap(), text(""));
+        assert_eq!(run("'ab' , ! 0").unwrap(), text("ab"));
+        assert_eq!(run("(0 $ <1) , 1 2").unwrap(), ints(vec![1, 2], vec![2]));
+
+        colored::control::set_override(false);
+        assert_eq!(run("0 3 $ 'a'").unwrap().unwrap().to_string(), "0 3 $ \"\"");
+        assert_eq!(run("0 $ 1").unwrap().unwrap().to_string(), "[]");
+    }
+
+    /// Whether `y` and everything it holds is in normal form, with no rank 0 arrays.
+    fn normal(y: &Val) -> bool {
+        match y {
+            Val::ValArr(a) => a.rank() > 0 && a.data.iter().all(normal),
+            Val::Unit(b) => normal(b),
+            Val::IntArr(Array { shape, .. }) | Val::FloatArr(Array { shape, .. }) | Val::ExtArr(Array { shape, .. })
+                | Val::AsciiArr(Array { shape, .. })
+                | Val::Utf16Arr(Array { shape, .. }) | Val::Utf32Arr(Array { shape, .. })
+                | Val::SymArr(Array { shape, .. }) => !shape.is_empty(),
+            _ => true,
+        }
+    }
+
+    #[test]
+    fn test_scalars() {
+        SPELL_IN_OUT.get_or_init(SpellInOut::init);
+        let prim = |p: PrimVerb, y: &Val| super::eval_mon(crate::Verb::Prim(p), y.clone(), &mut Env::default()).unwrap();
+        // every result of a primitive is normal, and its shape, length and rank agree
+        for w in words() {
+            for y in NOUNS {
+                let results = NOUNS.iter()
+                    .map(|x| format!("{x} {w} {y}"))
+                    .chain([format!("{w} {y}")])
+                    .filter_map(|src| eval(&src, &mut Env::default()).ok().flatten().map(|r| (src, r)));
+                for (src, r) in results {
+                    assert!(normal(&r), "{src} gave {r:?}");
+                    let Val::IntArr(shape) = prim(PrimVerb::dllr, &r) else { panic!("shape of {src}") };
+                    assert_eq!(prim(PrimVerb::hash_col, &r), Val::Int(shape.data.len() as i64), "{src}");
+                    assert_eq!(prim(PrimVerb::hash, &r), Val::Int(shape.data.first().map_or(1, |&n| n)), "{src}");
+                }
+            }
+        }
+        // rank 0 arrays however made are the scalars
+        for (a, b) in [("3", "(! 0) $ 3"), ("2.5", "h. 2.5 1"), ("'a'", "h. 'ab'"), ("`a", "h. `a`b"), ("3", "+/ 1 2")] {
+            assert_eq!(run(a).unwrap(), run(b).unwrap(), "{b}");
+            for w in words() {
+                let (ra, rb) = (run(&format!("{w} ({a})")), run(&format!("{w} ({b})")));
+                assert_eq!(ra.ok(), rb.ok(), "{w} {b}");
+            }
+        }
+        assert_eq!(run("(! 0) $ 1 2 ; 3").unwrap(), Some(Val::Unit(Box::new(ints(vec![1, 2], vec![2]).unwrap()))));
+        assert_eq!(run("$ (! 0) $ 'ab'").unwrap(), ints(vec![], vec![0]));
+        assert_eq!(run("#: 5").unwrap(), Some(Val::Int(0)));
+        assert_eq!(run("# 5").unwrap(), Some(Val::Int(1)));
+    }
+
+    #[test]
+    fn test_shared() {
+        SPELL_IN_OUT.get_or_init(SpellInOut::init);
+        let mut env = Env::default();
+        let atoms = |env: &Env, name: &str| match env.get(name) {
+            Some(Val::IntArr(a)) => a.data.as_ptr(),
+            y => panic!("{name} is {y:?}"),
+        };
+        eval("a =: ! 1000", &mut env).unwrap();
+        let before = atoms(&env, "a");
+        eval("a =: a + 1", &mut env).unwrap();
+        assert_eq!(atoms(&env, "a"), before);
+        eval("a =: 2 * a", &mut env).unwrap();
+        assert_eq!(atoms(&env, "a"), before);
+        assert_eq!(eval("h. a", &mut env).unwrap(), Some(Val::Int(2)));
+
+        // a copy shares the atoms until either is changed
+        eval("b =: a", &mut env).unwrap();
+        assert_eq!(atoms(&env, "b"), before);
+        eval("a =: a - 2", &mut env).unwrap();
+        assert_ne!(atoms(&env, "a"), before);
+        assert_eq!(eval("(h. a) , h. b", &mut env).unwrap(), ints(vec![0, 2], vec![2]));
+
+        // overflow still promotes, leaving the name intact
+        eval("c =: 1 2 , 9223372036854775807", &mut env).unwrap();
+        assert!(matches!(eval("c =: c + 1", &mut env).unwrap(), Some(Val::FloatArr(_))));
+        eval("d =: 1 2 3", &mut env).unwrap();
+        assert!(eval("d =: d + `x", &mut env).is_err());
+        assert_eq!(env.get("d"), ints(vec![1, 2, 3], vec![3]));
+        assert!(eval("d =: 1.2.3 + d", &mut env).is_err());
+        assert!(eval("e =: 1e + e", &mut env).is_err());
+        assert_eq!(env.get("d"), ints(vec![1, 2, 3], vec![3]));
+
+        // so may arithmetic between the name and any number
+        eval("f =: ! 1000", &mut env).unwrap();
+        let before = atoms(&env, "f");
+        eval("f =: f + h. f", &mut env).unwrap();
+        assert_eq!(atoms(&env, "f"), before);
+    }
+
+    #[test]
+    fn test_overflow() {
+        assert_eq!(run("9223372036854775807 + 1").unwrap(), Some(Val::Float(9223372036854775808.0)));
+        assert_eq!(run("_9223372036854775807 - 2").unwrap(), Some(Val::Float(-9223372036854775809.0)));
+        let r = run("1 2 4611686018427387904 * 2").unwrap();
+        assert_eq!(r, Some(Array { data: vec![2.0, 4.0, 9223372036854775808.0].into(), shape: vec![3] }.into()));
+        assert_eq!(run("1 2 3 * 2").unwrap(), ints(vec![2, 4, 6], vec![3]));
+        assert_eq!(run("99999999999999999999").unwrap(), Some(Val::Float(1e20)));
+
+        colored::control::set_override(false);
+        let show = |src: &str| run(src).unwrap().unwrap().to_string();
+        assert_eq!(show("9223372036854775807 + 1"), "9223372036854776000");
+        assert_eq!(show("1 % 0"), "_");
+        assert_eq!(show("_1 % 0"), "__");
+        assert_eq!(show("_ - _"), "_.");
+        assert_eq!(show("1 _1 0 % 0"), "[_ __ _.]");
+        assert_eq!(show("2.5"), "2.5");
+    }
+
+    #[test]
+    fn test_extended() {
+        let ext = |s: &str| Some(Val::Ext(s.parse().unwrap()));
+        assert_eq!(run("4611686018427387904x * 4").unwrap(), ext("18446744073709551616"));
+        assert_eq!(run("1x % 3").unwrap(), ext("1/3"));
+        assert_eq!(run("1r3 + 1r6").unwrap(), ext("1/2"));
+        assert_eq!(run("_1r2 * 2").unwrap(), ext("-1"));
+        assert_eq!(run("1r3 < 1r2").unwrap(), Some(Val::Int(1)));
+        assert_eq!(run("1r2 + 0.25").unwrap(), Some(Val::Float(0.75)));
+        assert_eq!(run("1x % 0").unwrap(), Some(Val::Float(f64::INFINITY)));
+        assert!(matches!(run("2x ^ 100000000"), Err(ALError::At { err, .. }) if matches!(*err, ALError::Limit(_))));
+        assert!(matches!(run("1r2 ^ _100000000"), Err(ALError::At { err, .. }) if matches!(*err, ALError::Limit(_))));
+        for src in ["1r0", "1r00", "1r_0", "1 2r00"] {
+            assert!(matches!(run(src), Err(ALError::At { err, .. }) if matches!(*err, ALError::Syntax(_))), "{src}");
+        }
+        let exts = |ns: &[i64]| Some(Val::ExtArr(ns.iter().map(|&n| super::ext::of(n)).collect::<Vec<_>>().into()));
+        assert_eq!(run("1x 2x 3x").unwrap(), exts(&[1, 2, 3]));
+        assert_eq!(run("1x + 1 2 3").unwrap(), exts(&[2, 3, 4]));
+        assert_eq!(run("1 2 , 3x").unwrap(), exts(&[1, 2, 3]));
+        assert_eq!(run("1r2 1 < 1").unwrap(), ints(vec![1, 0], vec![2]));
+        assert_eq!(run("- 1r2 2").unwrap().unwrap().to_string(), "[-1r2 -2]");
+        assert_eq!(run("_1 | _9223372036854775807 - 1").unwrap(), Some(Val::Int(0)));
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+
+use crate::{Array, Result, Val};
+use super::shape::{list, Shape};
+use super::sort::{keys, Key};
+
+pub trait Search where Self: Sized {
+    /// `x i. y`, the index of the first item of `x` matching each cell of `y`, `#x` where none does.
+    fn index_of(self, y: Self) -> Result<Self>;
+    /// `x e. y`, whether each cell of `x` is an item of `y`.
+    fn member_of(self, y: Self) -> Result<Self>;
+    /// `x i: y`, like `i.` but each item of `x` matches only one cell of `y`, the earliest.
+    fn progressive_index(self, y: Self) -> Result<Self>;
+    /// `x E. y`, a mask of the places in `y` where the subarray `x` begins.
+    fn find(self, y: Self) -> Result<Self>;
+    /// `~. y`, the distinct major cells of `y` in order of first appearance.
+    fn nub(self) -> Result<Self>;
+    /// `~: y`, whether each major cell of `y` is the first of its kind.
+    fn nub_sieve(self) -> Result<Self>;
+    /// `= y`, the index in the nub of each major cell of `y`.
+    fn classify(self) -> Result<Self>;
+    /// `#. y`, how many equal cells come before each major cell of `y`.
+    fn occurrences(self) -> Result<Self>;
+}
+
+/// Relative tolerance of float equality.
+const TOLERANCE: f64 = 1.0 / (1u64 << 44) as f64;
+
+fn tolerant(a: f64, b: f64) -> bool {
+    a == b || (a - b).abs() <= TOLERANCE * a.abs().max(b.abs())
+}
+
+/// Whether two atoms match, floats tolerantly.
+fn same(a: &Key, b: &Key) -> bool {
+    match (a, b) {
+        (Key::Float(_), _) | (_, Key::Float(_)) => a.class() == 0 && b.class() == 0 && tolerant(a.float(), b.float()),
+        (Key::Box(ash, a), Key::Box(bsh, b)) => ash == bsh && all_same(a, b),
+        (a, b) => a == b,
+    }
+}
+
+fn all_same(a: &[Key], b: &[Key]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b))
+}
+
+/// Whether the keys can be matched by hashing, that is they hold no floats.
+fn exact(k: &[Key]) -> bool {
+    k.iter().all(|k| match k {
+        Key::Float(_) => false,
+        Key::Box(_, k) => exact(k),
+        _ => true,
+    })
+}
+
+/// The items of `x` and the cells of `y` of the same rank, as keys, with the frame of the cells.
+struct Lookup {
+    frame: Vec<u32>,
+    items: Vec<Vec<Key>>,
+    cells: Vec<Vec<Key>>,
+    /// Whether the cells have the shape of the items, without which nothing matches.
+    fits: bool,
+}
+
+impl Lookup {
+    fn new(x: &Val, y: &Val) -> Result<Self> {
+        let xs = match x.shape_ref()[..] {
+            [] => vec![1],
+            ref s => s.to_vec(),
+        };
+        let item = &xs[1..];
+        let ys = y.shape_ref();
+        let fits = ys.len() >= item.len() && ys[ys.len() - item.len()..] == *item;
+        let frame = ys[..ys.len().saturating_sub(item.len())].to_vec();
+        let chunk = |k: Vec<Key>, n: usize| {
+            let size = k.len() / n.max(1);
+            if size == 0 { vec![Vec::new(); n] } else { k.chunks(size).map(<[Key]>::to_vec).collect_vec() }
+        };
+        let items = chunk(keys(x)?, xs[0] as usize);
+        let cells = if fits {
+            chunk(keys(y)?, frame.iter().product::<u32>() as usize)
+        } else {
+            vec![Vec::new(); frame.iter().product::<u32>() as usize]
+        };
+        Ok(Lookup { frame, items, cells, fits })
+    }
+
+    fn exact(&self) -> bool {
+        self.items.iter().chain(&self.cells).all(|k| exact(k))
+    }
+
+    /// The index of the first matching item for every cell, the number of items for none.
+    fn first(&self) -> Vec<i64> {
+        let n = self.items.len();
+        if !self.fits {
+            return vec![n as i64; self.cells.len()];
+        }
+        if self.exact() {
+            let mut first = HashMap::with_capacity(n);
+            for (i, item) in self.items.iter().enumerate() {
+                first.entry(&item[..]).or_insert(i);
+            }
+            return self.cells.iter().map(|c| *first.get(&c[..]).unwrap_or(&n) as i64).collect();
+        }
+        let atoms = |k: &Vec<Vec<Key>>| k.iter().all(|k| k.len() == 1 && k[0].class() == 0);
+        if atoms(&self.items) && atoms(&self.cells) {
+            return self.first_of_numbers();
+        }
+        self.cells.iter()
+            .map(|c| self.items.iter().position(|i| all_same(i, c)).unwrap_or(n) as i64)
+            .collect()
+    }
+
+    /// Looks numbers up in the items sorted by value, scanning the window of tolerance around each.
+    fn first_of_numbers(&self) -> Vec<i64> {
+        let n = self.items.len();
+        let values = self.items.iter().map(|i| i[0].float()).collect_vec();
+        let mut order = (0..n).collect_vec();
+        order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+        self.cells.iter()
+            .map(|c| {
+                let q = c[0].float();
+                let slack = if q.is_finite() { 2.0 * TOLERANCE * q.abs() } else { 0.0 };
+                let start = order.partition_point(|&i| values[i] < q - slack);
+                order[start..].iter()
+                    .take_while(|&&i| values[i] <= q + slack)
+                    .filter(|&&i| same(&self.items[i][0], &c[0]))
+                    .min()
+                    .map_or(n, |&i| i) as i64
+            })
+            .collect()
+    }
+
+    /// Like `first`, using every item up once.
+    fn progressive(&self) -> Vec<i64> {
+        let n = self.items.len();
+        if !self.fits {
+            return vec![n as i64; self.cells.len()];
+        }
+        if self.exact() {
+            let mut unused: HashMap<&[Key], Vec<usize>> = HashMap::with_capacity(n);
+            for (i, item) in self.items.iter().enumerate().rev() {
+                unused.entry(&item[..]).or_default().push(i);
+            }
+            return self.cells.iter()
+                .map(|c| unused.get_mut(&c[..]).and_then(Vec::pop).unwrap_or(n) as i64)
+                .collect();
+        }
+        let mut used = vec![false; n];
+        self.cells.iter()
+            .map(|c| match (0..n).find(|&i| !used[i] && all_same(&self.items[i], c)) {
+                Some(i) => {
+                    used[i] = true;
+                    i as i64
+                },
+                None => n as i64,
+            })
+            .collect()
+    }
+
+    fn result(&self, data: Vec<i64>) -> Val {
+        if self.frame.is_empty() {
+            Val::Int(data[0])
+        } else {
+            Array { data: data.into(), shape: self.frame.clone() }.into()
+        }
+    }
+}
+
+/// The index of the first cell equal to each major cell of `y`, scalars taken as lists of one.
+fn self_index(y: Val) -> Result<(Val, Vec<i64>)> {
+    let y = list(y);
+    let first = Lookup::new(&y, &y)?.first();
+    Ok((y, first))
+}
+
+impl Search for Val {
+    fn nub(self) -> Result<Val> {
+        let (y, first) = self_index(self)?;
+        let idx = first.iter().enumerate().filter(|&(i, &f)| i as i64 == f).map(|(i, _)| i as i64).collect_vec();
+        Val::IntArr(idx.into()).select(y)
+    }
+
+    fn nub_sieve(self) -> Result<Val> {
+        let (_, first) = self_index(self)?;
+        let data = first.iter().enumerate().map(|(i, &f)| (i as i64 == f) as i64).collect_vec();
+        Ok(Array::from(data).into())
+    }
+
+    fn classify(self) -> Result<Val> {
+        let (_, first) = self_index(self)?;
+        let mut class = vec![0i64; first.len()];
+        let mut n = 0;
+        for (i, &f) in first.iter().enumerate() {
+            if i as i64 == f {
+                class[i] = n;
+                n += 1;
+            } else {
+                class[i] = class[f as usize];
+            }
+        }
+        Ok(Array::from(class).into())
+    }
+
+    fn occurrences(self) -> Result<Val> {
+        let (_, first) = self_index(self)?;
+        let mut seen = vec![0i64; first.len()];
+        let data = first.iter()
+            .map(|&f| {
+                seen[f as usize] += 1;
+                seen[f as usize] - 1
+            })
+            .collect_vec();
+        Ok(Array::from(data).into())
+    }
+
+    fn index_of(self, y: Val) -> Result<Val> {
+        let l = Lookup::new(&self, &y)?;
+        Ok(l.result(l.first()))
+    }
+
+    fn member_of(self, y: Val) -> Result<Val> {
+        let l = Lookup::new(&y, &self)?;
+        let n = l.items.len() as i64;
+        Ok(l.result(l.first().into_iter().map(|i| (i < n) as i64).collect()))
+    }
+
+    fn progressive_index(self, y: Val) -> Result<Val> {
+        let l = Lookup::new(&self, &y)?;
+        Ok(l.result(l.progressive()))
+    }
+
+    fn find(self, y: Val) -> Result<Val> {
+        let ys = y.shape_ref().clone();
+        let mut xs = self.shape_ref().clone();
+        let size = ys.iter().product::<u32>() as usize;
+        let mut data = vec![0; size];
+        if xs.len() <= ys.len() {
+            xs.splice(0..0, vec![1; ys.len() - xs.len()]);
+            let (kx, ky) = (keys(&self)?, keys(&y)?);
+            // row-major strides of y, and the offsets of the atoms of x within y
+            let strides = (0..ys.len()).map(|a| ys[a + 1..].iter().product::<u32>() as usize).collect_vec();
+            let offsets = (0..kx.len())
+                .map(|mut i| {
+                    let mut off = 0;
+                    for a in (0..xs.len()).rev() {
+                        off += (i % xs[a] as usize) * strides[a];
+                        i /= xs[a] as usize;
+                    }
+                    off
+                })
+                .collect_vec();
+            for (p, d) in data.iter_mut().enumerate() {
+                let fits = (0..ys.len()).all(|a| (p / strides[a]) % ys[a] as usize + xs[a] as usize <= ys[a] as usize);
+                if fits && offsets.iter().zip(&kx).all(|(o, k)| same(k, &ky[p + o])) {
+                    *d = 1;
+                }
+            }
+        }
+        Ok(if ys.is_empty() { Val::Int(data[0]) } else { Array { data: data.into(), shape: ys }.into() })
+    }
+}
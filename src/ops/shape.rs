@@ -1,38 +1,121 @@
-use core::panic;
-use std::{fmt::Debug, iter::{once, repeat, zip}, ops::{self, Index, Mul, Range, RangeBounds, ShlAssign}, process::id, usize, vec};
+use std::{fmt::Debug, iter::{once, repeat, zip}, ops::{self, Index, Mul, Range, RangeBounds, ShlAssign}, process::id, vec};
 
-use crate::{Val, Array, Result, ALError};
+use crate::{atom_count, intern, Fill, Symbol, Val, Array, Result, ALError};
+use super::cells::Cells;
+use super::math::{exts, floats, ints};
 use itertools::{repeat_n, Itertools};
-use nix::libc::group;
 use num::{abs, iter::{self}, Float};
 use Val::*;
 
 
-pub trait Shape {
-    fn shape_mon(y: Val) -> Val;
+pub trait Shape where Self: Sized {
+    fn shape_mon(y: Val) -> Result<Val>;
     fn shape_ref(&self) -> &Vec<u32>;
-    fn shape_dyd(x: Val, y: Val) -> Val;
-
-    fn first(self) -> Self;
-    fn last(self) -> Self;
-    fn first_cell(self) -> Self;
-    fn last_cell(self) -> Self;
-    fn take(self, y: Val) -> Self;
-    fn drop(self, y: Val) -> Self;
+    fn shape_dyd(x: Val, y: Val) -> Result<Val>;
+
+    fn first(self) -> Result<Self>;
+    fn last(self) -> Result<Self>;
+    fn first_cell(self) -> Result<Self>;
+    fn last_cell(self) -> Result<Self>;
+    fn take(self, y: Val) -> Result<Self>;
+    fn drop(self, y: Val) -> Result<Self>;
     //fn tail(self) -> Self;
     //fn curtail(self) -> Self;
-    fn select(self, y: Val) -> Self;
-    fn pick(self, y: Val) -> Self;
+    fn select(self, y: Val) -> Result<Self>;
+    fn pick(self, y: Val) -> Result<Self>;
+}
+
+/// Matches `$y` against every array variant, running `$body` with the array bound to `$a`
+/// and handing anything else to the trailing arms.
+macro_rules! with_array {
+    ($y:expr, $a:ident => $body:expr, $($rest:pat $(if $guard:expr)? => $other:expr),+ $(,)?) => {
+        match $y {
+            IntArr($a) => $body,
+            FloatArr($a) => $body,
+            ExtArr($a) => $body,
+            AsciiArr($a) => $body,
+            Utf16Arr($a) => $body,
+            Utf32Arr($a) => $body,
+            ValArr($a) => $body,
+            SymArr($a) => $body,
+            $($rest $(if $guard)? => $other),+
+        }
+    };
+}
+
+/// Reads the shape `x` of a reshape of `n` atoms, where one `_` axis takes up what the others leave.
+fn new_shape(x: Val, n: usize) -> Result<Vec<u32>> {
+    // `_` is the one float an axis can be, besides a whole number
+    let axis = |f: f64| match f {
+        f64::INFINITY => Ok(None),
+        f if f.fract() == 0.0 && f.abs() < 9.2e18 => Ok(Some(f as i64)),
+        f => ALError::as_Domain(format!("bad shape {f}")),
+    };
+    let axes = match x {
+        Int(i) => vec![Some(i)],
+        IntArr(a) if a.rank() == 1 => a.data.iter().copied().map(Some).collect_vec(),
+        x@(Float(_) | FloatArr(_)) if x.shape_ref().len() <= 1 => floats(x)?.data.iter().map(|&f| axis(f)).collect::<Result<_>>()?,
+        x => return ALError::as_Domain(format!("cannot reshape into {x}")),
+    };
+    if let Some(i) = axes.iter().flatten().find(|&&i| i < 0 || i > u32::MAX as i64) {
+        return ALError::as_Domain(format!("bad shape {i}"));
+    }
+    let inferred = axes.iter().positions(Option::is_none).collect_vec();
+    let known = atom_count(axes.iter().flatten().map(|&i| i as u64))?;
+    let fill = match inferred[..] {
+        [] => 0,
+        [_] if known > 0 && n.is_multiple_of(known) => n / known,
+        [_] => return ALError::as_Length(format!("{n} atoms do not fill axes of {known}")),
+        _ => return ALError::as_Domain("only one axis can be inferred"),
+    };
+    Ok(axes.into_iter().map(|i| i.map_or(fill as u32, |i| i as u32)).collect())
+}
+
+/// Lays the atoms of `a` out in `shape`, repeating them as often as needed.
+fn reshape<T: Clone>(shape: Vec<u32>, a: Array<T>) -> Result<Array<T>> {
+    let n = atom_count(shape.iter().map(|&i| i as u64))?;
+    if a.data.is_empty() && n > 0 {
+        return ALError::as_Length(format!("cannot reshape an empty array into {shape:?}"));
+    }
+    let data = a.data.into_iter().cycle().take(n).collect_vec();
+    Ok(Array { data: data.into(), shape })
+}
+
+/// A scalar as a list of one, arrays unchanged.
+pub(super) fn list(y: Val) -> Val {
+    match y {
+        Int(y) => IntArr(vec![y].into()),
+        Float(y) => FloatArr(vec![y].into()),
+        Ext(y) => ExtArr(vec![y].into()),
+        Unit(b) => ValArr(vec![*b].into()),
+        Sym(s) => SymArr(Array { data: vec![s].into(), shape: vec![1] }),
+        Ascii(c) => AsciiArr(vec![c].into()),
+        Utf16(c) => Utf16Arr(vec![c].into()),
+        Utf32(c) => Utf32Arr(vec![c].into()),
+        y@ValFunc(_) => ValArr(vec![y].into()),
+        y => y,
+    }
 }
 
 pub trait Select where Self: Sized {
-    fn group(self, y:Self) -> Result<Self>;
+    /// `x ;. y`, the major cells of `y` gathered by the keys `x`, or the cells along several
+    /// axes by a list of keys for each.
+    fn group(self, y: Self) -> Result<Self>;
+    /// `;. y`, the indices in each group of the keys `y`.
+    fn group_indices(self) -> Result<Self>;
+    fn catalogue(self) -> Result<Self>;
+    fn amend(self, y: Self) -> Result<Self>;
 }
 
-fn  index<T: Into<Val> + Clone>(Array { data, shape }: &Array<T>, idx: usize, cells: bool) -> Val 
+/// Item `idx` of `a` if `cells`, otherwise atom `idx`, which is the fill when there are items but no atoms.
+fn  index<T: Into<Val> + Clone + Fill>(a: &Array<T>, idx: usize, cells: bool) -> Result<Val> 
 where Array<T>: Into<Val>
 {
-    if cells {
+    let Array { data, shape } = a;
+    if shape.first() == Some(&0) {
+        return ALError::as_Index("empty array has no items");
+    }
+    Ok(if cells {
         if shape.len() == 1 {
             let v = data[idx].clone().into();
             Val::Unit(Box::new(v))
@@ -40,47 +123,44 @@ where Array<T>: Into<Val>
             let nsh = &shape[1..];
             let step = shape[1..].iter().product::<u32>() as usize;
             Array {
-                data: data[idx * step .. ][0..step].to_vec(),
+                data: data[idx * step .. ][0..step].to_vec().into(),
                 shape: shape[1..].to_vec() 
             }.into()
         }
+    } else if data.is_empty() {
+        a.fill().into()
     } else {
         data[idx].clone().into()
-    }
+    })
 }
 
-fn  pick<T: Into<Val> + Clone>(Array { data, shape }: &Array<T>, idx: Vec<i64>) -> Val 
+fn  pick<T: Into<Val> + Clone>(Array { data, shape }: &Array<T>, idx: Vec<i64>) -> Result<Val> 
 where Array<T>: Into<Val> 
 {
     if shape.len() != idx.len() {
-        panic!("bad index");
-    }
-    let i: i64 = idx.into_iter()
-        .zip_eq(shape)
-        .map(|(mut i, &s)| {
-            let s = s as i64;
-            let r = -s..s;
-            if !r.contains(&i) {
-                panic!("bad")
-            }
-            if i < 0  {
-                i += s;
-            }
-            i * s
-    }).sum();
-    data[i as usize].clone().into()
+        return ALError::as_Rank(format!("pick needs {} indices, got {}", shape.len(), idx.len()));
+    }
+    let mut i = 0;
+    for (j, &s) in idx.into_iter().zip_eq(shape) {
+        let s = s as i64;
+        if j >= s || j < -s {
+            return ALError::as_Index(format!("{j} out of bounds for length {s}"));
+        }
+        i = i * s + if j < 0 { j + s } else { j };
+    }
+    Ok(data[i as usize].clone().into())
 }
 
-fn  select<T: Into<Val> + Clone>(a: Array<T>, idx: Array<i64>) -> Val 
+fn  select<T: Into<Val> + Clone>(a: &Array<T>, idx: Array<i64>) -> Result<Val> 
 where Array<T>: Into<Val> 
 {
-    if a.shape.len() == 1 && idx.shape.len() == 1 && idx.shape[0] == 1 {
-        return Box::new(a.cell(idx.data[0])[0].clone().into()).into();
+    if a.shape.len() == 1 && idx.shape.is_empty() {
+        return Ok(Box::new(a.cell(idx.data[0])?[0].clone().into()).into());
     }
 
     let mut data = Vec::new();
-    for i in idx.data {
-        data.extend_from_slice(a.cell(i))
+    for &i in &idx.data {
+        data.extend_from_slice(a.cell(i)?)
     }
 
     let mut shape = idx.shape.clone();
@@ -88,35 +168,34 @@ where Array<T>: Into<Val>
         shape.extend_from_slice(&a.shape[1..]);
     }
 
-    Array {
-        data,
+    Ok(Array {
+        data: data.into(),
         shape
-    }.into()
+    }.into())
 }
 
-fn  take<T: Into<Val> + Clone + Default>(Array { data, shape }: Array<T>, v: Vec<i64>) -> Val 
+fn  take<T: Into<Val> + Clone>(Array { data, shape }: Array<T>, v: Vec<i64>, fill: T) -> Result<Val> 
 where Array<T>: Into<Val> 
 {
     if let [i] = v[..] {
-        let cyc = once(T::default()).cycle();
+        let cyc = once(fill).cycle();
         let step = if shape.len() == 1 { 1 } 
         else { shape[1..].iter().product::<u32>() as usize };
 
-        let l = shape[0] as i64;
+        let l = shape[0] as u64;
         let mut shape = shape.clone();
 
-        let data = if abs(i) > l {
-            let extra = (l - abs(i)) as usize;
-            let v = data.clone();
-            shape[0] += extra as u32;
-            let extra = cyc.take(extra);
+        let data = if i.unsigned_abs() > l {
+            let n = atom_count(once(i.unsigned_abs()).chain(shape[1..].iter().map(|&a| a as u64)))?;
+            shape[0] = i.unsigned_abs() as u32;
+            let extra = cyc.take(n - data.len());
             if i < 0 {
-                extra.chain(data.into_iter()).collect_vec()
+                extra.chain(data).collect_vec()
             } else {
                 data.into_iter().chain(extra).collect_vec()
             }
         } else if i < 0 {
-            let i = (l + i) as usize;
+            let i = (l - i.unsigned_abs()) as usize;
             shape[0] -= i as u32;
             data[i*step..].to_vec()
         } else {
@@ -125,224 +204,305 @@ where Array<T>: Into<Val>
             data[0..i*step].to_vec()
         };
 
-        Array {
-            data,
+        Ok(Array {
+            data: data.into(),
             shape
-        }.into()
+        }.into())
     } else {
-        panic!("nyi")
+        ALError::as_Nyi("take along several axes")
     }
 }
 
-fn  drop<T: Into<Val> + Clone + Default>(Array { data, shape }: Array<T>, v: Vec<i64>) -> Val 
+fn  drop<T: Into<Val> + Clone>(Array { data, shape }: Array<T>, v: Vec<i64>) -> Result<Val> 
 where Array<T>: Into<Val> 
 {
     if let [i] = v[..] {
-        let l = shape[0] as i64;
+        let l = shape[0] as u64;
         let step = if shape.len() == 1 { 1 } 
         else { shape[1..].iter().product::<u32>() as usize };
         let mut shape = shape.clone();
 
-        let data = if abs(i) > l {
-            shape.fill(0);
+        let data = if i.unsigned_abs() >= l {
+            shape[0] = 0;
             Vec::new()
         } else if i < 0 {
-            let i = (l + i) as usize;
-            shape[0] += i as u32;
+            let i = (l - i.unsigned_abs()) as usize;
+            shape[0] = i as u32;
             data[0..i*step].to_vec()
         } else {
             let i = i as usize;
             shape[0] -= i as u32;
             data[i*step..].to_vec()
         };
-        Array {
-            data,
+        Ok(Array {
+            data: data.into(),
             shape
-        }.into()
+        }.into())
     } else {
-        panic!("nyi")
+        ALError::as_Nyi("drop along several axes")
     }
 }
 
 impl Select for Val {
-    fn group(self, y:Self) -> Result<Self> {
-        match self {
-            Int(x) => {
-                if let Unit(u) = y {
-                     group(
-                        x.into(),
-                        Array { data: vec![*u], shape: vec![1] }
-                    )
-                } else {
-                    ALError::as_Type(format!("y must be an array, got x: {x}, y: {y}"))
-                }
+    /// Every way of choosing one item from each box of a list, laid out in a table of boxes.
+    fn catalogue(self) -> Result<Self> {
+        let lists = match self {
+            ValArr(a) if a.rank() == 1 => a.data,
+            Unit(b) => vec![*b].into(),
+            y => return ALError::as_Domain(format!("catalogue needs a list of boxes, got {y}")),
+        };
+        let items = lists.into_iter()
+            .map(|l| {
+                let r = l.shape_ref().len();
+                l.split(r.max(1) - 1).map(|(_, items)| items)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let shape = items.iter().map(|i| i.len() as u32).collect_vec();
+        let data = items.iter()
+            .map(|i| i.iter().cloned())
+            .multi_cartesian_product()
+            .map(|choice| Val::assemble(vec![choice.len() as u32], choice))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Array { data: data.into(), shape }.into())
+    }
+
+    /// `(v ; i) } y` is `y` with its items at `i` replaced by the items of `v`, or by `v` itself.
+    fn amend(self, y: Val) -> Result<Self> {
+        let (v, i) = match self {
+            ValArr(a) if a.shape == [2] => {
+                let mut it = a.data.into_iter();
+                (it.next().unwrap(), it.next().unwrap())
             },
-            IntArr(x) => match y {
-                IntArr(a) => group(x, a),
-                FloatArr(a) => group(x, a),
-                AsciiArr(a) => group(x, a),
-                ValArr(a) => group(x, a),
-                Unit(u) => group(x, Array { data: vec![*u], shape: vec![1] }),
-                _ => ALError::as_Type(format!("y must be an array, got {y}")),
+            x => return ALError::as_Domain(format!("amend needs values ; indices, got {x}")),
+        };
+        let idx = match i {
+            Int(i) => vec![i],
+            IntArr(i) if i.rank() == 1 => i.data.into_vec(),
+            i => return ALError::as_Domain(format!("cannot amend at {i}")),
+        };
+        if mixes_text(&v, &y) {
+            return ALError::as_Domain(format!("cannot amend {y} with {v}"));
+        }
+        let rank = y.shape_ref().len();
+        if rank == 0 {
+            return ALError::as_Rank(format!("cannot amend the scalar {y}"));
+        }
+        let (frame, mut items) = y.split(rank - 1)?;
+        let values = if v.shape_ref().len() == rank - 1 {
+            vec![v; idx.len()]
+        } else {
+            v.split(rank - 1)?.1
+        };
+        if values.len() != idx.len() {
+            return ALError::as_Length(format!("{} values for {} indices", values.len(), idx.len()));
+        }
+        let n = items.len() as i64;
+        for (i, v) in idx.into_iter().zip(values) {
+            if i >= n || i < -n {
+                return ALError::as_Index(format!("{i} out of bounds for length {n}"));
             }
-            x => ALError::as_Value(format!("cannot group using: {}", x)),
+            items[i.rem_euclid(n) as usize] = v;
         }
+        Val::assemble(frame, items)
     }
-}
 
-fn group<T: Debug + Into<Val> + Clone>(x: Array<i64>, y: Array<T>) -> Result<Val> 
-where Array<T>: Into<Val> 
-{
-    let xs = x.shape;
-    if xs.len() > 1 {
-        panic!("nyi");
-    } else if xs[0] < y.shape[0] {
-        return ALError::as_Shape("group: x len must be equal to y len");
-    }
-    if xs[0] > y.shape[0] { 
-        panic!("nyi");
+    fn group(self, y: Self) -> Result<Self> {
+        let ys = y.shape_ref().clone();
+        let (frame, axes) = match self {
+            ValArr(x) if x.rank() == 1 => {
+                let keys = x.data.into_iter().map(group_keys).collect::<Result<Vec<_>>>()?;
+                if keys.len() > ys.len() {
+                    return ALError::as_Rank(format!("cannot group the rank {} array along {} axes", ys.len(), keys.len()));
+                }
+                let axes = zip(&keys, &ys)
+                    .map(|(k, &n)| match k.rank() {
+                        1 => buckets(&k.data, n as usize),
+                        _ => ALError::as_Rank("the keys for each axis must be a list"),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                (ys[..keys.len()].to_vec(), axes)
+            },
+            ValArr(_) => return ALError::as_Rank("the keys for each axis must form a list"),
+            x => {
+                let k = group_keys(x)?;
+                let mut frame = k.shape.clone();
+                if let ([n], [m, ..]) = (&mut frame[..], &ys[..]) {
+                    *n = (*n).min(*m);
+                }
+                if frame.is_empty() || !ys.starts_with(&frame) {
+                    return ALError::as_Length(format!("cannot group the shape {ys:?} by keys of shape {:?}", k.shape));
+                }
+                let n = frame.iter().product::<u32>() as usize;
+                (frame, vec![buckets(&k.data, n)?])
+            },
+        };
+        // the grouped axes merge into one, the key axes each stepping through it with a stride
+        let strides = match axes.len() {
+            1 => vec![1],
+            m => (0..m).map(|a| frame[a + 1..].iter().product::<u32>() as i64).collect_vec(),
+        };
+        let merged = once(frame.iter().product::<u32>()).chain(ys[frame.len()..].iter().copied()).collect_vec();
+        let idx = axes.iter()
+            .map(|b| 0..b.len())
+            .multi_cartesian_product()
+            .map(|g| {
+                let parts = zip(&axes, &g).map(|(b, &i)| &b[i]).collect_vec();
+                let data = parts.iter()
+                    .zip(&strides)
+                    .map(|(p, &s)| p.iter().map(move |&i| i * s))
+                    .multi_cartesian_product()
+                    .map(|p| p.iter().sum())
+                    .collect_vec();
+                Array { data: data.into(), shape: parts.iter().map(|p| p.len() as u32).collect() }
+            })
+            .collect_vec();
+        let groups = with_array!(y, a => {
+                let a = reshape(merged, a)?;
+                idx.into_iter().map(|i| select(&a, i)).collect::<Result<Vec<_>>>()?
+            },
+            y => return ALError::as_Rank(format!("cannot group the scalar {y}")),
+        );
+        Ok(Array { data: groups.into(), shape: axes.iter().map(|b| b.len() as u32).collect() }.into())
     }
 
-    let mut res: Vec<Array<T>> = Vec::new();
+    fn group_indices(self) -> Result<Self> {
+        let shape = match &self {
+            ValArr(x) if x.rank() == 1 => x.data.iter().map(|k| k.shape_ref().first().copied().unwrap_or(1)).collect_vec(),
+            x => x.shape_ref().clone(),
+        };
+        let y = match shape[..] {
+            [n] => IntArr((0..n as i64).collect_vec().into()),
+            _ => {
+                let data = shape.iter()
+                    .map(|&n| 0..n as i64)
+                    .multi_cartesian_product()
+                    .map(|i| IntArr(i.into()))
+                    .collect();
+                ValArr(Array { data, shape })
+            },
+        };
+        self.group(y)
+    }
+}
 
-    for i  in  0..xs[0] { 
-        let v = y.cell(i as i64);
-        let i = x.data[i as usize];
-        if i != -1 {
-            let i = i as usize;
-            if res.len() <= i {
-                res.resize(i + 1, Array::default());
+/// Reads the keys of a group, whole numbers of which `_1` leaves a cell out.
+fn group_keys(x: Val) -> Result<Array<i64>> {
+    let k = match x {
+        Int(_) | IntArr(_) => ints(x)?,
+        Float(_) | FloatArr(_) => {
+            let a = floats(x)?;
+            match a.data.iter().map(|&f| (f.fract() == 0.0 && f.abs() < 9.2e18).then_some(f as i64)).collect() {
+                Some(data) => Array { data, shape: a.shape },
+                None => return ALError::as_Domain("cannot group by fractional keys"),
             }
-            let a = &mut res[i];
-            a.data.extend_from_slice(v);
-            if y.shape.len() > 1 && a.shape[0] == 0 {
-                a.shape.extend_from_slice(&y.shape[1..]);
-            }
-            a.shape[0] += 1;
-            dbg!(&res);
-        }
+        },
+        x => return ALError::as_Domain(format!("cannot group by {x}")),
+    };
+    match k.data.iter().find(|&&i| i < -1) {
+        Some(i) => ALError::as_Domain(format!("bad group key {i}")),
+        None => Ok(k),
     }
+}
 
-    let res = res.into_iter()
-        .map_into::<Val>()
-        //.map(|v| Val::Unit(Box::new(v)))
-        .collect_vec();
-    Ok(Array::from(res).into())
+/// The positions along an axis of length `n` in each group. One key more than `n`
+/// is the least number of groups, padded with empty ones.
+fn buckets(k: &[i64], n: usize) -> Result<Vec<Vec<i64>>> {
+    let (k, len) = match k.len() {
+        l if l == n => (k, 0),
+        l if l == n + 1 && k[n] >= 0 => (&k[..n], k[n]),
+        l if l == n + 1 => return ALError::as_Domain(format!("bad group length {}", k[n])),
+        l => return ALError::as_Length(format!("{l} group keys for an axis of length {n}")),
+    };
+    let count = k.iter().map(|&i| i.saturating_add(1)).max().unwrap_or(0).max(len);
+    let mut groups = vec![Vec::new(); atom_count([count as u64])?];
+    for (p, &i) in k.iter().enumerate() {
+        if i >= 0 {
+            groups[i as usize].push(p as i64);
+        }
+    }
+    Ok(groups)
 }
 
 impl Shape for Val {
-    fn pick(self, y: Val) -> Self {
-        match self {
-            Int(x) => match y {
-                IntArr(a) => pick(&a, vec![x]),
-                FloatArr(a) => pick(&a, vec![x]),
-                _ => panic!("nyi"),
-            },
-            IntArr(x) if x.shape.len() == 1 => match y {
-                IntArr(a) => pick(&a, x.data),
-                FloatArr(a) => pick(&a, x.data),
-                Unit(u) if x.shape[0] == 0 => *u,
-                _ => panic!("nyi"),
-            }
-            _ => panic!("cannot pick using {self:?}"),
-        }
+    fn pick(self, y: Val) -> Result<Self> {
+        let idx = match self {
+            Int(x) => vec![x],
+            IntArr(x) if x.shape.len() == 1 => x.data.into_vec(),
+            x => return ALError::as_Domain(format!("cannot pick using {x}")),
+        };
+        with_array!(y, a => pick(&a, idx),
+            Unit(u) if idx.is_empty() => Ok(*u),
+            y if idx.is_empty() => Ok(y),
+            y => ALError::as_Rank(format!("pick needs no indices into {y}, got {}", idx.len())),
+        )
     }
 
-    fn select(self, y: Val) -> Self {
-        match self {
-            Int(x) => match y {
-                IntArr(a) => select(a, x.into()),
-                FloatArr(a) => select(a, x.into()),
-                _ => panic!("nyi"),
-            },
-            IntArr(x) => match y {
-                IntArr(a) => select(a, x),
-                FloatArr(a) => select(a, x),
-                _ => panic!("nyi"),
-            },
-            ValArr(x) => panic!("nyi"),
-            _ => panic!("cannot select using {self:?}"),
-        }
+    fn select(self, y: Val) -> Result<Self> {
+        let idx = match self {
+            Int(x) => Array { data: vec![x].into(), shape: Vec::new() },
+            IntArr(x) => x,
+            ValArr(x) => return ALError::as_Nyi("select using boxes"),
+            x => return ALError::as_Domain(format!("cannot select using {x}")),
+        };
+        with_array!(y, a => select(&a, idx),
+            y => ALError::as_Rank(format!("cannot select from the scalar {y}")),
+        )
     }
 
-    fn first(self) -> Self {
-        match self {
-            IntArr(a)  =>  index(&a, 0, false),
-            FloatArr(a)  =>  index(&a, 0, false),
-            Unit(b) => *b,
-            y@(Int(_) | Float(_)) => y,
-            _ => todo!("nyi"),
-        }
+    fn first(self) -> Result<Self> {
+        with_array!(self, a => index(&a, 0, false),
+            Unit(b) => Ok(*b),
+            y => Ok(y),
+        )
     }
 
-    fn first_cell(self) -> Self {
-        match self {
-            IntArr(a)  =>  index(&a, 0, true),
-            FloatArr(a)  =>  index(&a, 0, true),
-            _ => todo!("nyi"),
-        }
+    fn first_cell(self) -> Result<Self> {
+        with_array!(self, a => index(&a, 0, true),
+            y => Ok(y),
+        )
     }
 
-    fn last(self) -> Self {
-        match self {
-            IntArr(a)  =>  index(&a, a.data.len() - 1, false) ,
-            FloatArr(a)  =>  index(&a, a.data.len()-1, false) ,
-            Unit(b) => *b,
-            y@(Int(_) | Float(_)) => y,
-            _ => todo!("nyi"),
-        }
+    fn last(self) -> Result<Self> {
+        with_array!(self, a => index(&a, a.data.len().saturating_sub(1), false),
+            Unit(b) => Ok(*b),
+            y => Ok(y),
+        )
     }
 
-    fn last_cell(self) -> Self {
-        match self {
-            IntArr(a)  =>  index(&a, (a.shape[0] - 1) as usize, true),
-            FloatArr(a)  =>  index(&a, (a.shape[0] - 1) as usize, true),
-            _ => todo!("nyi"),
-        }
+    fn last_cell(self) -> Result<Self> {
+        with_array!(self, a => index(&a, a.shape[0].saturating_sub(1) as usize, true),
+            y => Ok(y),
+        )
     }
 
-    fn take(self, y: Val) -> Self {
-        match self {
-             Int(i) =>  match y {
-                IntArr(a) => take(a, vec![i]),
-                FloatArr(a) => take(a, vec![i]),
-                Int(a) => take(vec![a].into(), vec![i]),
-                Float(a) => take(vec![a].into(), vec![i]),
-                _ => panic!("cannot take from {y:?}"),
-            },
-            IntArr(i) => match y {
-                IntArr(a) => take(a, i.data),
-                FloatArr(a) => take(a, i.data),
-                Int(a) => take(vec![a].into(), i.data),
-                Float(a) => take(vec![a].into(), i.data),
-                _ => panic!("cannot take from {y:?}"),
-            }
-            _ => panic!("cannot take using {self:?}"),
-        }
+    fn take(self, y: Val) -> Result<Self> {
+        let n = match self {
+            Int(i) => vec![i],
+            IntArr(i) if i.shape.len() == 1 => i.data.into_vec(),
+            x => return ALError::as_Domain(format!("cannot take using {x}")),
+        };
+        with_array!(list(y), a => { let fill = a.fill(); take(a, n, fill) },
+            y => ALError::as_Nyi(format!("take from {y}")),
+        )
     }
 
-    fn drop(self, y: Val) -> Self {
-        match self {
-             Int(i) =>  match y {
-                IntArr(a) => drop(a, vec![i]),
-                FloatArr(a) => drop(a, vec![i]),
-                Int(a) => drop(vec![a].into(), vec![i]),
-                Float(a) => drop(vec![a].into(), vec![i]),
-                _ => panic!("cannot drop from {y:?}"),
-            },
-            IntArr(i) => match y {
-                IntArr(a) => drop(a, i.data),
-                FloatArr(a) => drop(a, i.data),
-                Int(a) => drop(vec![a].into(), i.data),
-                Float(a) => drop(vec![a].into(), i.data),
-                _ => panic!("cannot drop from {y:?}"),
-            }
-            _ => panic!("cannot drop using {self:?}"),
-        }
+    fn drop(self, y: Val) -> Result<Self> {
+        let n = match self {
+            Int(i) => vec![i],
+            IntArr(i) if i.shape.len() == 1 => i.data.into_vec(),
+            x => return ALError::as_Domain(format!("cannot drop using {x}")),
+        };
+        with_array!(list(y), a => drop(a, n),
+            y => ALError::as_Nyi(format!("drop from {y}")),
+        )
     }
 
-    fn shape_dyd(x: Val, y: Val) -> Val {
-        panic!("nyii");
+    fn shape_dyd(x: Val, y: Val) -> Result<Val> {
+        let n = y.shape_ref().iter().product::<u32>() as usize;
+        let shape = new_shape(x, n)?;
+        with_array!(list(y), a => Ok(reshape(shape, a)?.into()),
+            y => ALError::as_Domain(format!("cannot reshape {y}")),
+        )
     }
 
     fn shape_ref(&self) -> &Vec<u32> {
@@ -351,65 +511,186 @@ impl Shape for Val {
         match self {
             IntArr(Array { data: _, shape }) | 
             AsciiArr(Array { data: _, shape }) | 
-            FloatArr(Array { data: _, shape }) => {
+            Utf16Arr(Array { data: _, shape }) | 
+            Utf32Arr(Array { data: _, shape }) | 
+            SymArr(Array { data: _, shape }) | 
+            ValArr(Array { data: _, shape }) | 
+            FloatArr(Array { data: _, shape }) |
+            ExtArr(Array { data: _, shape }) => {
                 shape
             },
-            Int(_) | Float(_) => {
-                &EMPTY_SHAPE
-            } ,
-            _ => panic!("nyi"),
+            _ => &EMPTY_SHAPE,
         }
     }
 
-    fn shape_mon(y: Val) -> Val {
-        match y {
-            IntArr(Array { data: _, shape }) | 
-            ValArr(Array { data: _, shape }) | 
-            AsciiArr(Array { data: _, shape }) | 
-            FloatArr(Array { data: _, shape }) => {
-                let data = shape.into_iter()
-                .map(|i| i as i64)
-                .collect_vec();
-                Array { shape: vec![data.len() as u32], data }.into()
-            },
-            Int(_) | Float(_) => Array { shape: vec![0], data: Vec::<i64>::new() }.into(),
-            _ => panic!("nyi: {y:?}"),
-        }
+    fn shape_mon(y: Val) -> Result<Val> {
+        let data = y.shape_ref()
+            .iter()
+            .map(|&i| i as i64)
+            .collect_vec();
+        Ok(Array { shape: vec![data.len() as u32], data: data.into() }.into())
     }
 }
 
 pub trait Length {
-    fn length_mon(y: Val) -> Val;
-    fn length_dyd(x: Val, y: Val) -> Val;
+    fn length_mon(y: Val) -> Result<Val>;
+    fn length_dyd(x: Val, y: Val) -> Result<Val>;
 }
 
 impl Length for Val {
-    fn length_dyd(x: Val, y: Val) -> Val {
-        panic!("nyi");
+    fn length_dyd(x: Val, y: Val) -> Result<Val> {
+        ALError::as_Nyi("dyadic #")
     }
 
-    fn length_mon(y: Val) -> Val {
+    fn length_mon(y: Val) -> Result<Val> {
         use Val::*;
-        match y {
-            IntArr(Array { data: _, shape }) | 
-            AsciiArr(Array { data: _, shape }) | 
-            FloatArr(Array { data: _, shape }) => Int(shape[0] as i64),
-            Int(_) | Float(_) => Int(1),
-            _ => panic!("nyi"),
-        }
+        Ok(Int(y.shape_ref().first().copied().unwrap_or(1) as i64))
     }
 }
 
 pub trait Rank {
-    fn rank(x: &Val) -> Val {
-        use Val::*;
-        match x {
-            Int(_) | Float(_) | Unit(_) => Int(0),
-            IntArr(Array { data: _, shape }) | 
-            FloatArr(Array { data: _, shape }) => Int(shape.len() as i64),
-            _ => panic!("nyi"),
-        }
+    fn rank(x: &Val) -> Result<Val> {
+        Ok(Val::Int(x.shape_ref().len() as i64))
     }
 }
 
 impl Rank for Val {}
+
+pub trait Join where Self: Sized {
+    fn ravel(self) -> Result<Self>;
+    fn append(self, y: Self) -> Result<Self>;
+    fn raze(self) -> Result<Self>;
+    fn link(self, y: Self) -> Result<Self>;
+    fn enclose(self) -> Self;
+    fn disclose(self) -> Result<Self>;
+}
+
+/// Joins `x` and `y` along their first axis. An argument one rank short is taken as a single item,
+/// and an atom is repeated to fill one.
+fn append<T: Clone>(x: Array<T>, y: Array<T>) -> Result<Array<T>> {
+    let long = if x.rank() >= y.rank() { &x } else { &y };
+    let r = long.rank().max(1);
+    let item = long.shape.get(1..).unwrap_or_default().to_vec();
+    let items = |a: Array<T>| -> Result<Array<T>> {
+        if a.rank() == r && a.shape[1..] == item[..] {
+            Ok(a)
+        } else if a.rank() + 1 == r && a.shape == item {
+            Ok(Array { data: a.data, shape: once(1).chain(item.iter().copied()).collect() })
+        } else if a.rank() == 0 {
+            let n = item.iter().product::<u32>() as usize;
+            Ok(Array { data: vec![a.data[0].clone(); n].into(), shape: once(1).chain(item.iter().copied()).collect() })
+        } else {
+            ALError::as_Length(format!("cannot join items of shape {:?} and {:?}", &a.shape[1..], item))
+        }
+    };
+    let (mut x, y) = (items(x)?, items(y)?);
+    x.data.extend(y.data);
+    x.shape[0] += y.shape[0];
+    Ok(x)
+}
+
+/// An array of a single type of atom, an atom as a rank 0 array.
+fn atoms<T>(y: Val) -> Array<T> where T: TryFrom<Val>, Array<T>: TryFrom<Val> {
+    if y.shape_ref().is_empty() {
+        Array { data: T::try_from(y).into_iter().collect(), shape: Vec::new() }
+    } else {
+        Array::try_from(y).unwrap_or_default()
+    }
+}
+
+/// Whether `x` and `y` put text together with numbers or symbols, which no flat array holds.
+fn mixes_text(x: &Val, y: &Val) -> bool {
+    let plain = |v: &Val| !v.is_text() && !matches!(v, ValArr(_) | Unit(_) | ValFunc(_));
+    x.is_text() && plain(y) || y.is_text() && plain(x)
+}
+
+/// `y` as an array of its atoms, each in a box.
+fn boxes(y: Val) -> Result<Array<Val>> {
+    Ok(match y {
+        ValArr(a) => a,
+        Unit(b) => Array { data: vec![*b].into(), shape: Vec::new() },
+        y => {
+            let (shape, data) = y.split(0)?;
+            Array { data: data.into(), shape }
+        }
+    })
+}
+
+impl Join for Val {
+    fn ravel(self) -> Result<Self> {
+        with_array!(self, a => Ok(Array { shape: vec![a.data.len() as u32], data: a.data }.into()),
+            y => Ok(list(y)),
+        )
+    }
+
+    fn append(self, y: Val) -> Result<Val> {
+        let num = |v: &Val| matches!(v, Int(_) | IntArr(_) | Float(_) | FloatArr(_) | Ext(_) | ExtArr(_));
+        let exact = |v: &Val| matches!(v, Int(_) | IntArr(_) | Ext(_) | ExtArr(_));
+        let int = |v: &Val| matches!(v, Int(_) | IntArr(_));
+        // an empty argument has no atoms to give the result a type, so it takes the other's
+        let x = match self.empty_shape().map(<[u32]>::to_vec) {
+            Some(shape) => y.empty(shape),
+            None => self,
+        };
+        let y = match y.empty_shape().map(<[u32]>::to_vec) {
+            Some(shape) if x.empty_shape().is_none() => x.empty(shape),
+            _ => y,
+        };
+        Ok(match (x, y) {
+            (x, y) if mixes_text(&x, &y) => return ALError::as_Domain(format!("cannot join {x} and {y}")),
+            (x, y) if int(&x) && int(&y) => append(ints(x)?, ints(y)?)?.into(),
+            (x, y) if exact(&x) && exact(&y) => append(exts(x)?, exts(y)?)?.into(),
+            (x, y) if num(&x) && num(&y) => append(floats(x)?, floats(y)?)?.into(),
+            (x@(Ascii(_) | AsciiArr(_)), y@(Ascii(_) | AsciiArr(_))) => append(atoms::<u8>(x), atoms::<u8>(y))?.into(),
+            (x@(Utf16(_) | Utf16Arr(_)), y@(Utf16(_) | Utf16Arr(_))) => append(atoms::<u16>(x), atoms::<u16>(y))?.into(),
+            (x@(Utf32(_) | Utf32Arr(_)), y@(Utf32(_) | Utf32Arr(_))) => append(atoms::<u32>(x), atoms::<u32>(y))?.into(),
+            // text of different widths widens to the wider
+            (x@(Sym(_) | SymArr(_)), y@(Sym(_) | SymArr(_))) => append(atoms::<Symbol>(x), atoms::<Symbol>(y))?.into(),
+            (x, y) if x.is_text() && y.is_text() => {
+                let points = |v: Val| v.code_points().unwrap_or_default();
+                Val::chars(append(points(x), points(y))?)
+            },
+            (x, y) => append(boxes(x)?, boxes(y)?)?.into(),
+        })
+    }
+
+    fn raze(self) -> Result<Self> {
+        match self {
+            ValArr(a) => {
+                let mut items = a.data.into_iter();
+                let first = items.next().map_or(Ok(Val::default()), list_of)?;
+                items.try_fold(first, Val::append)
+            },
+            Unit(b) => list_of(*b),
+            y => y.ravel(),
+        }
+    }
+
+    fn link(self, y: Val) -> Result<Self> {
+        let mut data = vec![self];
+        match y {
+            ValArr(a) if a.rank() == 1 => data.extend(a.data),
+            Unit(b) => data.push(*b),
+            y => data.push(y),
+        }
+        Ok(Array::from(data).into())
+    }
+
+    fn enclose(self) -> Self {
+        Unit(Box::new(self))
+    }
+
+    /// Opens a box, or merges a list of boxes into one array whose items are their contents.
+    fn disclose(self) -> Result<Self> {
+        match self {
+            Unit(b) => Ok(*b),
+            ValArr(a) => Val::assemble(a.shape, a.data.into_vec()),
+            y => Ok(y),
+        }
+    }
+}
+
+/// `y` with rank at least 1.
+fn list_of(y: Val) -> Result<Val> {
+    if y.shape_ref().is_empty() { y.ravel() } else { Ok(y) }
+}
@@ -0,0 +1,174 @@
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+use itertools::Itertools;
+use num::{BigRational, ToPrimitive};
+
+use crate::{sym_text, ALError, Array, Result, Val};
+use super::shape::Shape;
+
+pub trait Sort where Self: Sized {
+    /// The permutation that puts the major cells of `y` in order, equal cells keeping their order.
+    fn grade(y: Self, down: bool) -> Result<Self>;
+    /// The major cells of `x` in the order of the keys `y`.
+    fn sort(self, y: Self, down: bool) -> Result<Self>;
+}
+
+/// An atom under the total array ordering: numbers come before characters,
+/// characters before symbols and symbols before boxes.
+/// Boxes compare their contents atom by atom, then by rank and shape.
+#[derive(Debug, Clone)]
+pub enum Key {
+    Int(i64),
+    Float(f64),
+    Ext(BigRational),
+    Char(u32),
+    Sym(String),
+    Box(Vec<u32>, Vec<Key>),
+}
+
+impl Key {
+    pub fn class(&self) -> u8 {
+        match self {
+            Key::Int(_) | Key::Float(_) | Key::Ext(_) => 0,
+            Key::Char(_) => 1,
+            Key::Sym(_) => 2,
+            Key::Box(..) => 3,
+        }
+    }
+
+    pub fn float(&self) -> f64 {
+        match self {
+            Key::Int(i) => *i as f64,
+            Key::Float(f) => *f,
+            Key::Ext(e) => e.to_f64().unwrap_or(f64::NAN),
+            _ => f64::NAN,
+        }
+    }
+}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> Ordering {
+        use Key::*;
+        match (self, other) {
+            (Int(a), Int(b)) => a.cmp(b),
+            (Ext(a), Ext(b)) => a.cmp(b),
+            (Ext(a), Int(b)) => a.cmp(&BigRational::from_integer((*b).into())),
+            (Int(a), Ext(b)) => BigRational::from_integer((*a).into()).cmp(b),
+            (Int(a), Float(b)) => int_float(*a, *b),
+            (Float(a), Int(b)) => int_float(*b, *a).reverse(),
+            (a, b) if a.class() == 0 && b.class() == 0 => a.float().total_cmp(&b.float()),
+            (Char(a), Char(b)) => a.cmp(b),
+            (Sym(a), Sym(b)) => a.cmp(b),
+            (Box(ash, a), Box(bsh, b)) => a.cmp(b).then(ash.len().cmp(&bsh.len())).then(ash.cmp(bsh)),
+            (a, b) => a.class().cmp(&b.class()),
+        }
+    }
+}
+
+/// Orders an integer against a float exactly, where converting the integer could round it.
+/// NaNs sit past the infinities on the side of their sign, as `f64::total_cmp` puts them.
+fn int_float(i: i64, f: f64) -> Ordering {
+    if f.is_nan() {
+        if f.is_sign_negative() { Ordering::Greater } else { Ordering::Less }
+    } else if f >= 9223372036854775808.0 {
+        Ordering::Less
+    } else if f < -9223372036854775808.0 {
+        Ordering::Greater
+    } else {
+        let t = f.trunc();
+        i.cmp(&(t as i64)).then(t.partial_cmp(&f).unwrap_or(Ordering::Equal))
+    }
+}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Key {}
+
+/// The atoms of `y` in ravel order.
+pub fn keys(y: &Val) -> Result<Vec<Key>> {
+    use Val::*;
+    Ok(match y {
+        Int(i) => vec![Key::Int(*i)],
+        Float(f) => vec![Key::Float(*f)],
+        Ext(e) => vec![Key::Ext(e.clone())],
+        Sym(s) => vec![Key::Sym(sym_text(*s))],
+        Ascii(c) => vec![Key::Char(*c as u32)],
+        Utf16(c) => vec![Key::Char(*c as u32)],
+        Utf32(c) => vec![Key::Char(*c)],
+        IntArr(a) => a.data.iter().map(|&i| Key::Int(i)).collect(),
+        FloatArr(a) => a.data.iter().map(|&f| Key::Float(f)).collect(),
+        ExtArr(a) => a.data.iter().map(|e| Key::Ext(e.clone())).collect(),
+        AsciiArr(a) => a.data.iter().map(|&c| Key::Char(c as u32)).collect(),
+        Utf16Arr(a) => a.data.iter().map(|&c| Key::Char(c as u32)).collect(),
+        Utf32Arr(a) => a.data.iter().map(|&c| Key::Char(c)).collect(),
+        SymArr(a) => a.data.iter().map(|&s| Key::Sym(sym_text(s))).collect(),
+        ValArr(a) => a.data.iter().map(boxed).collect::<Result<_>>()?,
+        Unit(b) => vec![boxed(b)?],
+        ValFunc(f) => return ALError::as_Domain(format!("cannot order the function {f}")),
+    })
+}
+
+/// The contents of a box as a single key, atoms standing for themselves.
+fn boxed(y: &Val) -> Result<Key> {
+    let shape = y.shape_ref().clone();
+    let mut k = keys(y)?;
+    Ok(if shape.is_empty() && k.len() == 1 && !matches!(y, Val::Unit(_)) {
+        k.remove(0)
+    } else {
+        Key::Box(shape, k)
+    })
+}
+
+impl Sort for Val {
+    fn grade(y: Val, down: bool) -> Result<Val> {
+        let Some(&n) = y.shape_ref().first() else {
+            return ALError::as_Rank(format!("cannot grade the scalar {y}"));
+        };
+        let keys = keys(&y)?;
+        let cell = keys.len() / Ord::max(n as usize, 1);
+        let cells = if cell == 0 { vec![&keys[..]; n as usize] } else { keys.chunks(cell).collect_vec() };
+        let mut idx = (0..n as i64).collect_vec();
+        idx.sort_by(|&a, &b| {
+            let o = cells[a as usize].cmp(cells[b as usize]);
+            if down { o.reverse() } else { o }
+        });
+        Ok(Array::from(idx).into())
+    }
+
+    fn sort(self, y: Val, down: bool) -> Result<Val> {
+        let (xn, yn) = (self.shape_ref().first().copied(), y.shape_ref().first().copied());
+        if xn != yn {
+            return ALError::as_Length(format!("{} items to sort by {} keys", xn.unwrap_or(1), yn.unwrap_or(1)));
+        }
+        Val::grade(y, down)?.select(self)
+    }
+}
+
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // equal exact keys hash alike, so whole rationals hash as the integer they equal;
+        // floats are compared tolerantly and never looked up by hash
+        match self {
+            Key::Int(i) => (0u8, i).hash(state),
+            Key::Ext(e) => match e.to_integer().to_i64() {
+                Some(i) if e.is_integer() => (0u8, i).hash(state),
+                _ => (1u8, e).hash(state),
+            },
+            Key::Float(f) => (2u8, f.to_bits()).hash(state),
+            Key::Char(c) => (3u8, c).hash(state),
+            Key::Sym(s) => (4u8, s).hash(state),
+            Key::Box(shape, k) => (5u8, shape, k).hash(state),
+        }
+    }
+}
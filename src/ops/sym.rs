@@ -0,0 +1,51 @@
+use itertools::Itertools;
+
+use crate::{intern, sym_text, ALError, Array, Result, Val};
+use super::cells::Cells;
+use super::shape::Shape;
+
+pub trait Symbols where Self: Sized {
+    /// `s: y`, the symbol named by the string `y`, each row of a table or each boxed string.
+    fn symbol(y: Self) -> Result<Self>;
+    /// `s. y`, the name of each symbol in `y` as a string, boxed for an array of symbols.
+    fn name(y: Self) -> Result<Self>;
+}
+
+impl Symbols for Val {
+    fn symbol(y: Val) -> Result<Val> {
+        use Val::*;
+        let name = |y: Val| match String::try_from(y) {
+            Ok(s) => Ok(intern(&s)),
+            Err(_) => ALError::as_Domain("only strings name symbols"),
+        };
+        match y {
+            y@(Sym(_) | SymArr(_)) => Ok(y),
+            y@(Ascii(_) | Utf16(_) | Utf32(_)) => Ok(Sym(name(y)?)),
+            y if y.is_text() && y.shape_ref().len() == 1 => Ok(Sym(name(y)?)),
+            y if y.is_text() => {
+                let (frame, rows) = y.split(1)?;
+                let data = rows.into_iter().map(name).collect::<Result<Vec<_>>>()?;
+                Ok(SymArr(Array { data: data.into(), shape: frame }))
+            },
+            ValArr(a) => {
+                let data = a.data.into_iter()
+                    .map(|y| match y {
+                        Sym(s) => Ok(s),
+                        y => name(y),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(SymArr(Array { data: data.into(), shape: a.shape }))
+            },
+            y => ALError::as_Domain(format!("cannot make a symbol of {y}")),
+        }
+    }
+
+    fn name(y: Val) -> Result<Val> {
+        let text = |s| -> Val { sym_text(s).chars().collect_vec().into() };
+        match y {
+            Val::Sym(s) => Ok(text(s)),
+            Val::SymArr(a) => Ok(Array { data: a.data.into_iter().map(text).collect(), shape: a.shape }.into()),
+            y => ALError::as_Domain(format!("only symbols have names, not {y}")),
+        }
+    }
+}
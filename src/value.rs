@@ -1,15 +1,33 @@
 //#![feature(trace_macros)]
 use itertools::{intersperse, Format, Itertools};
 use nix::NixPath;
-use num::{abs, cast::AsPrimitive, range_step, traits::{ops::overflowing::OverflowingMul, SaturatingSub}, PrimInt, Saturating};
+use num::{BigRational, abs, cast::AsPrimitive, range_step, traits::{ops::overflowing::OverflowingMul, SaturatingSub}, PrimInt, Saturating};
 use rustyline::{line_buffer::WordAction, Word};
 use string_interner::{backend::{BucketBackend, StringBackend}, StringInterner};
 use core::fmt;
-use std::{any::TypeId, fmt::{Debug, Display, Write}, isize, marker::PhantomData, ops::{self, *}, os::unix::fs::OpenOptionsExt, process::{id, Output}, u32, usize, vec::IntoIter};
+use std::{any::TypeId, fmt::{Debug, Display, Write}, marker::PhantomData, ops::{self, *}, os::unix::fs::OpenOptionsExt, process::{id, Output}, vec::IntoIter};
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::mem::{Discriminant, discriminant};
+use std::iter;
 use colored::Colorize;
 
-type Symbol = string_interner::DefaultSymbol;
+pub type Symbol = string_interner::DefaultSymbol;
+
+thread_local! {
+    /// The names of the symbols, each interned once.
+    static SYMS: RefCell<StringInterner<BucketBackend>> = RefCell::new(StringInterner::new());
+}
+
+/// The symbol named `name`.
+pub fn intern(name: &str) -> Symbol {
+    SYMS.with_borrow_mut(|syms| syms.get_or_intern(name))
+}
+
+/// The name of the symbol `s`.
+pub fn sym_text(s: Symbol) -> String {
+    SYMS.with_borrow(|syms| syms.resolve(s).unwrap_or_default().to_string())
+}
 
 use crate::{eval::Token, verb::Verb, ALError, Adverb, Conj, PrimConj};
 
@@ -20,18 +38,38 @@ pub enum Func {
     C(Conj),
 }
 
+impl fmt::Display for Func {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Func::V(v) => f.write_fmt(format_args!("{v}")),
+            Func::A(a) => f.write_fmt(format_args!("{a}")),
+            Func::C(c) => f.write_fmt(format_args!("{c}")),
+        }
+    }
+}
+
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Val {
     Int(i64),
     Float(f64),
+    /// Extended precision rational, written `123x` or `1r3`.
+    Ext(BigRational),
     Sym(Symbol),
+    /// A character, in the narrowest of the widths of text arrays that holds it.
+    Ascii(u8),
+    Utf16(u16),
+    Utf32(u32),
     AsciiArr(Array<u8>),
     Utf16Arr(Array<u16>),
     Utf32Arr(Array<u32>),
     IntArr(Array<i64>),
     FloatArr(Array<f64>),
+    /// Extended precision rationals, a strand holding any `x` or `r` number.
+    ExtArr(Array<BigRational>),
+    #[allow(clippy::enum_variant_names)]
     ValArr(Array<Val>),
+    #[allow(clippy::enum_variant_names)]
     ValFunc(Func),
     Unit(Box<Val>),
     SymArr(Array<Symbol>),
@@ -83,7 +121,7 @@ impl  <T: From<Val> + Clone> TryFrom<Val> for GenericVal<T> {
 }
 */
 
-#[macro_export()]
+#[macro_export]
 macro_rules! is_arr {
     ($bind:ident) => {
         AsciiArr($bind) |
@@ -97,38 +135,35 @@ impl TryFrom<Array<u8>> for String {
     type Error = ALError;
     fn try_from(Array { data, shape }: Array<u8>) -> Result<Self, Self::Error>{
         if shape.len() > 1 {
-            Err(ALError::Shape(format!("cannot make string from rank {:?} Array", shape.len())))
+            Err(ALError::Rank(format!("cannot make string from rank {:?} Array", shape.len())))
         } else {
-            Ok(String::from_utf8(data).unwrap())
+            Ok(data.into_iter().map(char::from).collect())
         }
     }
 }
 
+impl TryFrom<Val> for String {
+    type Error = ALError;
+    fn try_from(y: Val) -> Result<Self, Self::Error> {
+        use Val::*;
+        match y {
+            Ascii(c) => Ok(char::from(c).to_string()),
+            Utf16(c) => Ok(char::from_u32(c.into()).into_iter().collect()),
+            Utf32(c) => Ok(char::from_u32(c).into_iter().collect()),
+            AsciiArr(a) if a.rank() == 1 => Ok(a.data.into_iter().map(char::from).collect()),
+            Utf16Arr(a) if a.rank() == 1 => Ok(a.data.into_iter().filter_map(|c| char::from_u32(c as u32)).collect()),
+            Utf32Arr(a) if a.rank() == 1 => Ok(a.data.into_iter().filter_map(char::from_u32).collect()),
+            y => Err(ALError::Domain(format!("not a string: {y}"))),
+        }
+    }
+}
 
 impl From<Vec<char>> for Val {
     fn from(y: Vec<char>) -> Self {
-        match y.iter().max() {
-            Some(c) => {
-                let c = c.clone() as u32;
-                if c <= u8::MAX as u32 {
-                    Array {
-                        shape: vec![y.len() as u32],
-                        data: y.into_iter().map(|c| c as u8).collect_vec(),
-                    }.into()
-                } else if c <= u16::MAX as u32 {
-                    Array {
-                        shape: vec![y.len() as u32],
-                        data: y.into_iter().map(|c| c as u16).collect_vec(),
-                    }.into()
-                } else {
-                    Array {
-                        shape: vec![y.len() as u32],
-                        data: y.into_iter().map(|c| c as u32).collect_vec(),
-                    }.into()
-                }
-            },
-            None => Array::<u8>::default().into(),
-        }
+        Val::chars(Array {
+            shape: vec![y.len() as u32],
+            data: y.into_iter().map(u32::from).collect(),
+        })
     }
 }
 
@@ -140,7 +175,7 @@ macro_rules! impl_conv {
     ($($tag:ident-$tp:ty);+) => {
 $(
         impl From<$tp> for Val {
-        fn from(y: $tp) -> Self { Val::$tag(y) }
+        fn from(y: $tp) -> Self { Val::$tag(y).normal() }
         }
         impl TryFrom<Val> for $tp {
         type Error = ();
@@ -159,41 +194,26 @@ $(
 impl_conv!(
     Int-i64;
     Float-f64;
+    Ext-BigRational;
+    Sym-Symbol;
+    SymArr-Array<Symbol>;
+    Ascii-u8;
+    Utf16-u16;
+    Utf32-u32;
     AsciiArr-Array<u8>;
+    Utf16Arr-Array<u16>;
+    Utf32Arr-Array<u32>;
     IntArr-Array<i64>;
-    FloatArr-Array<f64>
+    FloatArr-Array<f64>;
+    ExtArr-Array<BigRational>
 );
 
 impl From<char> for Val {
-    // todo char val?
-    fn from(y: char) -> Self { Val::Int(y as i64) }
-}
-
-impl From<u8> for Val {
-    // todo char val?
-    fn from(y: u8) -> Self { Val::Int(y as i64) }
-}
-
-impl TryFrom<Val> for u8 {
-    // todo char val?
-    type Error = ();
-    fn try_from(v: Val) -> Result<Self, Self::Error> {
-        if let Val::Int(y) = v {
-            u8::try_from(y).map_err(|_|())
-        } else {
-            Err(())
-        }
+    fn from(y: char) -> Self {
+        Val::chars(Array { data: vec![y.into()].into(), shape: Vec::new() })
     }
 }
 
-impl From<Array<u16>> for Val {
-    fn from(y: Array<u16>) -> Self { Val::Utf16Arr(y) }
-}
-
-impl From<Array<u32>> for Val {
-    fn from(y: Array<u32>) -> Self { Val::Utf32Arr(y) }
-}
-
 /*
 impl From<Array<char>> for Val {
     fn from(y: Array<char>) -> Self { Val::AsciiArr(y) }
@@ -201,18 +221,251 @@ impl From<Array<char>> for Val {
 */
 
 impl From<Array<Val>> for Val {
-    fn from(y: Array<Val>) -> Self { Val::ValArr(y) }
+    fn from(y: Array<Val>) -> Self { Val::ValArr(y).normal() }
+}
+
+/// The fill of a boxed array, an empty list.
+impl Default for Val {
+    fn default() -> Self { Val::IntArr(Array::default()) }
+}
+
+impl Val {
+    /// Text of the code points in `a`, in the narrowest width that holds them all,
+    /// a character if `a` has rank 0.
+    pub fn chars(a: Array<u32>) -> Val {
+        fn narrow<T: TryFrom<u32>>(Array { data, shape }: Array<u32>) -> Array<T> {
+            Array { data: data.into_iter().filter_map(|c| T::try_from(c).ok()).collect(), shape }
+        }
+        let scalar = a.shape.is_empty();
+        match a.data.iter().max().copied().unwrap_or(0) {
+            c if c <= u8::MAX as u32 => match narrow::<u8>(a) {
+                a if scalar => Val::Ascii(a.data[0]),
+                a => a.into(),
+            },
+            c if c <= u16::MAX as u32 => match narrow::<u16>(a) {
+                a if scalar => Val::Utf16(a.data[0]),
+                a => a.into(),
+            },
+            _ if scalar => Val::Utf32(a.data[0]),
+            _ => a.into(),
+        }
+    }
+
+    /// The same value with a rank 0 array as the atom it holds, a box for one of boxes,
+    /// so that every value has a single form.
+    pub fn normal(self) -> Val {
+        use Val::*;
+        fn atom<T: Clone>(a: Array<T>) -> T {
+            a.data.into_vec().swap_remove(0)
+        }
+        match self {
+            IntArr(a) if a.rank() == 0 => Int(atom(a)),
+            FloatArr(a) if a.rank() == 0 => Float(atom(a)),
+            ExtArr(a) if a.rank() == 0 => Ext(atom(a)),
+            AsciiArr(a) if a.rank() == 0 => Ascii(atom(a)),
+            Utf16Arr(a) if a.rank() == 0 => Utf16(atom(a)),
+            Utf32Arr(a) if a.rank() == 0 => Utf32(atom(a)),
+            SymArr(a) if a.rank() == 0 => Sym(atom(a)),
+            ValArr(a) if a.rank() == 0 => Unit(Box::new(atom(a))),
+            y => y,
+        }
+    }
+
+    /// An array of `shape`, which holds no atoms, of the type of `self`.
+    pub fn empty(&self, shape: Vec<u32>) -> Val {
+        use Val::*;
+        match self {
+            Int(_) | IntArr(_) => IntArr(Array { data: Vec::new().into(), shape }),
+            Float(_) | FloatArr(_) => FloatArr(Array { data: Vec::new().into(), shape }),
+            Ext(_) | ExtArr(_) => ExtArr(Array { data: Vec::new().into(), shape }),
+            Ascii(_) | AsciiArr(_) => AsciiArr(Array { data: Vec::new().into(), shape }),
+            Utf16(_) | Utf16Arr(_) => Utf16Arr(Array { data: Vec::new().into(), shape }),
+            Utf32(_) | Utf32Arr(_) => Utf32Arr(Array { data: Vec::new().into(), shape }),
+            Sym(_) | SymArr(_) => SymArr(Array { data: Vec::new().into(), shape }),
+            ValArr(_) | ValFunc(_) | Unit(_) => ValArr(Array { data: Vec::new().into(), shape }),
+        }
+    }
+
+    /// The shape of an array without atoms.
+    pub fn empty_shape(&self) -> Option<&[u32]> {
+        use Val::*;
+        let shape = match self {
+            IntArr(a) if a.data.is_empty() => &a.shape,
+            FloatArr(a) if a.data.is_empty() => &a.shape,
+            ExtArr(a) if a.data.is_empty() => &a.shape,
+            AsciiArr(a) if a.data.is_empty() => &a.shape,
+            Utf16Arr(a) if a.data.is_empty() => &a.shape,
+            Utf32Arr(a) if a.data.is_empty() => &a.shape,
+            SymArr(a) if a.data.is_empty() => &a.shape,
+            ValArr(a) if a.data.is_empty() => &a.shape,
+            _ => return None,
+        };
+        Some(shape)
+    }
+
+    /// Whether this is a character or an array of them.
+    pub fn is_text(&self) -> bool {
+        use Val::*;
+        matches!(self, Ascii(_) | Utf16(_) | Utf32(_) | AsciiArr(_) | Utf16Arr(_) | Utf32Arr(_))
+    }
+
+    /// The code points of text, a character giving a rank 0 array.
+    pub fn code_points(self) -> Option<Array<u32>> {
+        use Val::*;
+        let scalar = |c: u32| Array { data: vec![c].into(), shape: Vec::new() };
+        Some(match self {
+            Ascii(c) => scalar(c.into()),
+            Utf16(c) => scalar(c.into()),
+            Utf32(c) => scalar(c),
+            AsciiArr(a) => a.cast(),
+            Utf16Arr(a) => a.cast(),
+            Utf32Arr(a) => a,
+            _ => return None,
+        })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Array<T> {
-    pub data: Vec<T>,
+    pub data: Data<T>,
     pub shape: Vec<u32>,
 }
 
 impl <T> Default for Array<T> {
     fn default() -> Self {
-        Array { data: vec![], shape: vec![0] }
+        Array { data: Vec::new().into(), shape: vec![0] }
+    }
+}
+
+/// The atoms of an array, shared by its copies so that copying one costs nothing.
+/// Writing through a copy gives it atoms of its own first, unless it is their only owner.
+#[derive(PartialEq)]
+pub struct Data<T>(Rc<Vec<T>>);
+
+impl <T> Data<T> {
+    /// Whether no other array shares these atoms, so they can be written in place.
+    pub fn is_unique(&self) -> bool {
+        Rc::strong_count(&self.0) == 1
+    }
+}
+
+impl <T: Clone> Data<T> {
+    pub fn into_vec(self) -> Vec<T> {
+        Rc::unwrap_or_clone(self.0)
+    }
+}
+
+impl <T> Clone for Data<T> {
+    fn clone(&self) -> Self {
+        Data(Rc::clone(&self.0))
+    }
+}
+
+impl <T: Debug> Debug for Data<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl <T> Deref for Data<T> {
+    type Target = Vec<T>;
+    fn deref(&self) -> &Vec<T> {
+        &self.0
+    }
+}
+
+impl <T: Clone> DerefMut for Data<T> {
+    fn deref_mut(&mut self) -> &mut Vec<T> {
+        Rc::make_mut(&mut self.0)
+    }
+}
+
+impl <T> From<Vec<T>> for Data<T> {
+    fn from(data: Vec<T>) -> Self {
+        Data(Rc::new(data))
+    }
+}
+
+impl <T> FromIterator<T> for Data<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Data(Rc::new(iter.into_iter().collect()))
+    }
+}
+
+impl <T: Clone> IntoIterator for Data<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+    fn into_iter(self) -> IntoIter<T> {
+        self.into_vec().into_iter()
+    }
+}
+
+impl <'a, T> IntoIterator for &'a Data<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+/// The element an array is padded with where it has none, such as in an overtake: BQN's fill.
+pub trait Fill where Self: Sized {
+    fn fill(a: &Array<Self>) -> Self;
+}
+
+impl Fill for i64 {
+    fn fill(_: &Array<i64>) -> i64 { 0 }
+}
+
+impl Fill for f64 {
+    fn fill(_: &Array<f64>) -> f64 { 0.0 }
+}
+
+impl Fill for BigRational {
+    fn fill(_: &Array<BigRational>) -> BigRational { BigRational::default() }
+}
+
+impl Fill for u8 {
+    fn fill(_: &Array<u8>) -> u8 { b' ' }
+}
+
+impl Fill for u16 {
+    fn fill(_: &Array<u16>) -> u16 { b' '.into() }
+}
+
+impl Fill for u32 {
+    fn fill(_: &Array<u32>) -> u32 { b' '.into() }
+}
+
+impl Fill for Symbol {
+    fn fill(_: &Array<Symbol>) -> Symbol { intern("") }
+}
+
+/// Boxes are padded with an empty list of the type of the first.
+impl Fill for Val {
+    fn fill(a: &Array<Val>) -> Val {
+        a.data.first().map_or_else(Val::default, |y| y.empty(vec![0]))
+    }
+}
+
+/// Most atoms a single array may hold.
+pub const MAX_ATOMS: u64 = 1 << 28;
+
+/// The number of atoms in an array of `axes`, or a limit error past [`MAX_ATOMS`].
+pub fn atom_count(axes: impl IntoIterator<Item = u64>) -> Result<usize, ALError> {
+    let axes = axes.into_iter().collect_vec();
+    if axes.contains(&0) {
+        return Ok(0);
+    }
+    match axes.iter().try_fold(1u64, |n, &a| n.checked_mul(a)) {
+        Some(n) if n <= MAX_ATOMS => Ok(n as usize),
+        _ => ALError::as_Limit(format!("an array of shape {axes:?} is too large")),
+    }
+}
+
+impl <T: Fill> Array<T> {
+    pub fn fill(&self) -> T {
+        T::fill(self)
     }
 }
 
@@ -221,15 +474,11 @@ impl <T> Array<T> {
         self.shape.len()
     }
 
-    pub fn is_single(&self) -> bool {
-        self.shape.len() == 1 && self.shape[0] == 1
-    }
-
-    pub fn cell(&self, idx: i64) -> &[T] {
+    pub fn cell(&self, idx: i64) -> Result<&[T], ALError> {
         let &Array { data, shape } = &self;
-        let l = shape[0] as i64;
-        if abs(idx) >= l {
-            panic!("bad index");
+        let l = shape.first().copied().unwrap_or(1) as i64;
+        if idx >= l || idx < -l {
+            return ALError::as_Index(format!("{idx} out of bounds for length {l}"));
         }
 
         let idx = if idx < 0 {
@@ -240,11 +489,26 @@ impl <T> Array<T> {
 
         let step = if shape.len() == 1 { 1 } 
         else { shape[1..].iter().product::<u32>() as usize };
-        &data[idx * step.. (idx + 1) * step]
+        Ok(&data[idx * step.. (idx + 1) * step])
     }
 
-    pub fn cast<G: Into<Val> + From<T>>(self) -> Array<G> {
-        Array { data: self.data.into_iter().map(G::from).collect_vec(), shape: self.shape }
+    /// Splits the array into its cells of rank `r`, along with the frame they are laid out in.
+    pub fn cells(self, r: usize) -> Result<(Vec<u32>, Vec<Array<T>>), ALError> where T: Clone {
+        let r = r.min(self.rank());
+        let (frame, cell_shape) = self.shape.split_at(self.shape.len() - r);
+        let n = frame.iter().product::<u32>();
+        let flat = Array {
+            data: self.data,
+            shape: iter::once(n).chain(cell_shape.iter().copied()).collect_vec(),
+        };
+        let cells = (0..n as i64)
+            .map(|i| flat.cell(i).map(|c| Array { data: c.to_vec().into(), shape: cell_shape.to_vec() }))
+            .collect::<Result<_, _>>()?;
+        Ok((frame.to_vec(), cells))
+    }
+
+    pub fn cast<G: Into<Val> + From<T>>(self) -> Array<G> where T: Clone {
+        Array { data: self.data.into_iter().map(G::from).collect(), shape: self.shape }
     }
 }
 
@@ -252,27 +516,84 @@ impl fmt::Display for Val {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use Val::*;
         match self {
+            // an empty list shows its type, other empties the reshape that makes them
+            y if y.empty_shape().is_some_and(|s| s != [0]) => {
+                let shape = y.empty_shape().unwrap_or_default().iter().join(" ");
+                f.write_fmt(format_args!("{shape} $ {}", y.empty(vec![0])))
+            },
             Int(y) => f.write_fmt(format_args!("{}", y)),
+            Float(y) => f.write_str(&show_float(*y)),
+            Ext(y) => f.write_str(&show_ext(y)),
+            ExtArr(a) => {
+                let numbers = a.data.iter().map(show_ext).collect();
+                f.write_fmt(format_args!("{}", Array { data: numbers, shape: a.shape.clone() }))
+            },
             Unit(y) => f.write_fmt(format_args!("<{}>", y)),
             IntArr(y) => f.write_fmt(format_args!("{}", y)),
             ValArr(y) => f.write_fmt(format_args!("v{}v", y)),
-            FloatArr(y) => f.write_fmt(format_args!("{}", y)),
-            AsciiArr(y) => {
-                if y.shape.len() == 1 {
-                    let s = std::str::from_utf8(&y.data[..]).unwrap().escape_default();
-                    let s = s.to_string().blue();
-                    f.write_fmt(format_args!("\"{s}\""))
-                } else {
-                    f.write_fmt(format_args!("{}", y))
-                }
+            FloatArr(a) => {
+                let numbers = a.data.iter().map(|&y| show_float(y)).collect();
+                f.write_fmt(format_args!("{}", Array { data: numbers, shape: a.shape.clone() }))
+            },
+            AsciiArr(y) => show_text(y, f),
+            Utf16Arr(y) => show_text(y, f),
+            Utf32Arr(y) => show_text(y, f),
+            ValFunc(y) => f.write_fmt(format_args!("{y}")),
+            Sym(s) => f.write_fmt(format_args!("`{}", sym_text(*s))),
+            SymArr(a) => {
+                let names = a.data.iter().map(|&s| format!("`{}", sym_text(s))).collect();
+                f.write_fmt(format_args!("{}", Array { data: names, shape: a.shape.clone() }))
             },
-            y => f.write_fmt(format_args!("{:?}", y)),
+            Ascii(c) => f.write_fmt(format_args!("'{}'", escape(&[*c]).blue())),
+            Utf16(c) => f.write_fmt(format_args!("'{}'", escape(&[*c]).blue())),
+            Utf32(c) => f.write_fmt(format_args!("'{}'", escape(&[*c]).blue())),
         }
     }
 }
 
-type Grid<T = char> = Vec<Vec<T>>;
-type Metagrid = Grid<Grid>;
+/// A float the way J writes the ones Rust would not: `_` and `__` for the infinities, `_.` for NaN.
+fn show_float(y: f64) -> String {
+    match y {
+        f64::INFINITY => "_".to_string(),
+        f64::NEG_INFINITY => "__".to_string(),
+        y if y.is_nan() => "_.".to_string(),
+        y => y.to_string(),
+    }
+}
+
+/// A rational the way it is written, `1r3`, or just its numerator when it is whole.
+fn show_ext(y: &BigRational) -> String {
+    if y.is_integer() {
+        y.numer().to_string()
+    } else {
+        format!("{}r{}", y.numer(), y.denom())
+    }
+}
+
+/// Code points as printable text, escaping what would not show.
+fn escape<T: Copy + Into<u32>>(text: &[T]) -> String {
+    text.iter()
+        .map(|&c| char::from_u32(c.into()).unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect::<String>()
+        .escape_debug()
+        .to_string()
+}
+
+/// A string in quotes, and a table of text as an array of its rows.
+fn show_text<T: Copy + Into<u32>>(a: &Array<T>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match a.shape[..] {
+        [_] => f.write_fmt(format_args!("\"{}\"", escape(&a.data).blue())),
+        [ref frame @ .., n] => {
+            let rows = match n {
+                0 => vec![String::from("\"\""); frame.iter().product::<u32>() as usize],
+                n => a.data.chunks(n as usize).map(|r| format!("\"{}\"", escape(r))).collect(),
+            };
+            f.write_fmt(format_args!("{}", Array { data: rows.into(), shape: frame.to_vec() }))
+        },
+        [] => f.write_fmt(format_args!("'{}'", escape(&a.data).blue())),
+    }
+}
+
 //let mut sz: winsize = winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
 //let ok = unsafe { termsize(stdout().as_raw_fd(), &mut sz) };
 
@@ -286,7 +607,7 @@ impl <T: Display> fmt::Display for Array<T> {
 
         let mut idx: Vec<_> = Vec::new();
         
-        fn recur<T: Display>(arr: &Array<T>, f: & mut fmt::Formatter<'_>, index: &mut Vec<usize>, hanging_indent: String, curr_width: usize) -> Result<String, fmt::Error> {
+        fn recur<T: Display>(arr: &Array<T>, index: &mut Vec<usize>, hanging_indent: String, curr_width: usize) -> Result<String, fmt::Error> {
             let axis = index.len();
             let axes_left = arr.shape.len() - axis;
 
@@ -324,7 +645,7 @@ impl <T: Display> fmt::Display for Array<T> {
                 let mut line = hanging_indent.clone();
                 for i in 0..leading_items {
                     index.push(i);
-                    let word = recur(arr, f, index, next_hanging_indent.clone(), next_width)?;
+                    let word = recur(arr, index, next_hanging_indent.clone(), next_width)?;
                     _ = index.pop();
                     // _extendLine_pretty?
                     line.push_str(&word);
@@ -339,14 +660,14 @@ impl <T: Display> fmt::Display for Array<T> {
                 for i in range_step(trailing_items as isize, 1, -1){
                     let i = i as usize;
                     index.push(a_len-i);
-                    let word = recur(arr, f, index, next_hanging_indent.clone(), next_width)?;
+                    let word = recur(arr, index, next_hanging_indent.clone(), next_width)?;
                     _ = index.pop();
                     // _extendLine_pretty?
                     line.push_str(&word);
                     line.push_str(separator);
                 }
                 index.push(a_len-1);
-                let word = recur(arr, f, index, next_hanging_indent.clone(), next_width)?;
+                let word = recur(arr, index, next_hanging_indent.clone(), next_width)?;
                 _ = index.pop();
                 // _extendLine_pretty?
                 line.push_str(&word);
@@ -358,13 +679,13 @@ impl <T: Display> fmt::Display for Array<T> {
                 for i in a_len.saturating_sub(trailing_items)..last {
                     *index.last_mut().unwrap() = i;
                     // _extendLine_pretty?
-                    let word = recur(arr, f, index, next_hanging_indent.clone(), next_width).unwrap();
+                    let word = recur(arr, index, next_hanging_indent.clone(), next_width).unwrap();
                     extend_line(&mut s, &mut line, word, elem_width, hanging_indent.clone());
                     line.push_str(separator);
                 }
                 if a_len != 0 {
                     *index.last_mut().unwrap() = last;
-                    let word = recur(arr, f, index, next_hanging_indent.clone(), next_width).unwrap();
+                    let word = recur(arr, index, next_hanging_indent.clone(), next_width).unwrap();
                     extend_line(&mut s, &mut line, word, elem_width, hanging_indent.clone());
                 }
                 s.push_str(&line);
@@ -373,7 +694,7 @@ impl <T: Display> fmt::Display for Array<T> {
                 let line_sep = format!("{}{}", separator, "\n".repeat(axes_left - 1));
                 for i in 0..leading_items {
                     index.push(i);
-                    let nested = recur(arr, f, index, next_hanging_indent.clone(), next_width)?;
+                    let nested = recur(arr, index, next_hanging_indent.clone(), next_width)?;
                     index.pop();
                     let nested = format!("{hanging_indent}{nested}{line_sep}");
                     s.push_str(&nested);
@@ -406,7 +727,7 @@ impl <T: Display> fmt::Display for Array<T> {
                 index.push(0);
                 let nested = ((a_len.saturating_sub(trailing_items))..a_len).map(|i| {
                     *index.last_mut().unwrap() = i;
-                    let mut nested = recur(arr, f, index, next_hanging_indent.clone(), next_width).unwrap();
+                    let mut nested = recur(arr, index, next_hanging_indent.clone(), next_width).unwrap();
                     nested.insert_str(0, &hanging_indent);
                     nested
                 }).join(&line_sep);
@@ -417,27 +738,26 @@ impl <T: Display> fmt::Display for Array<T> {
             Ok(format!("[{}]", s.split_off(hanging_indent.len())))
         }
 
-        let grid = recur(self, f, &mut idx, String::from(" "), line_width)?;
+        let grid = recur(self, &mut idx, String::from(" "), line_width)?;
         f.write_str(&grid)
     }
 }
 
-fn extend_line(mut s: &mut String, mut line: &mut String, word: String, line_width: usize, next_line_prefix: String) {
+fn extend_line(s: &mut String, line: &mut String, word: String, line_width: usize, next_line_prefix: String) {
     let words = word.lines().collect_vec();
     if let [w] = words[..]  {
         if line.len() + w.len() > line_width {
-            s.push_str(&line);
+            s.push_str(line);
             s.push('\n');
             line.clone_from(&next_line_prefix)
         }
         line.push_str(&word);
     } else {
         let max_word_length = words.iter().map(|w| w.len()).max().unwrap_or(0);
-        println!("l: {}, mx: {}, w: {}, pre: {}", line.len(), max_word_length, line_width, next_line_prefix.len());
         //println!("w: {word}");
         let indent = if line.len() + max_word_length > line_width 
         && line.len() > next_line_prefix.len() {
-            s.push_str(&line);
+            s.push_str(line);
             s.push('\n');
 
             *line = next_line_prefix.clone();
@@ -450,7 +770,7 @@ fn extend_line(mut s: &mut String, mut line: &mut String, word: String, line_wid
         };
 
         for word in &words[1..] {
-            s.push_str(&line);
+            s.push_str(line);
             s.push('\n');
             *line = indent.clone();
             line.push_str(word);
@@ -459,11 +779,6 @@ fn extend_line(mut s: &mut String, mut line: &mut String, word: String, line_wid
 
 }
 
-#[derive(Debug)]
-pub enum ValueError {
-    Conversion,
-}
-
 impl <T: Into<Val>> FromIterator<T> for Array<T>
     where Array<T>: From<Vec<T>>
 {
@@ -475,8 +790,8 @@ impl <T: Into<Val>> FromIterator<T> for Array<T>
 impl From<i64> for Array<f64> {
     fn from(i: i64) -> Self {
         Array {
-            data: vec![i as f64],
-            shape: vec![1],
+            data: vec![i as f64].into(),
+            shape: Vec::new(),
         }
     }
 }
@@ -484,13 +799,13 @@ impl From<i64> for Array<f64> {
 impl From<Array<i64>> for Array<f64> {
     fn from(Array { data, shape }: Array<i64>) -> Self {
         Array {
-            data: data.into_iter().map(i64::as_).collect_vec(),
+            data: data.into_iter().map(i64::as_).collect(),
             shape,
         }
     }
 }
 
-impl<T> IntoIterator for Array<T> {
+impl<T: Clone> IntoIterator for Array<T> {
     type Item = T;
     type IntoIter = IntoIter<Self::Item>;
     fn into_iter(self) -> Self::IntoIter {
@@ -504,8 +819,8 @@ macro_rules! impl_from_arr {
         impl From<$tp> for Array<$tp> {
             fn from(y: $tp) -> Self {
                 Array {
-                    data: vec![y],
-                    shape: vec![1],
+                    data: vec![y].into(),
+                    shape: Vec::new(),
                 }
             }
         }
@@ -513,18 +828,18 @@ macro_rules! impl_from_arr {
             fn from(y: Vec<$tp>) -> Self {
                 Array {
                     shape: vec![y.len() as u32],
-                    data: y,
+                    data: y.into(),
                 }
             }
         }
         impl From<&[$tp]> for Array<$tp> {
             fn from(y: &[$tp]) -> Self {
                 Array {
-                    data: y.to_vec(),
+                    data: y.to_vec().into(),
                     shape: vec![y.len() as u32],
                 }
             }
         })+
     };
 }
-impl_from_arr!(f64, i64, u8, u16, u32, Val);
+impl_from_arr!(f64, i64, u8, u16, u32, Val, Symbol, BigRational);
@@ -7,7 +7,9 @@ use std::{collections::HashMap, hash::Hash};
 use std::collections::hash_map;
 use colored::Colorize;
 
-use crate::{eval::{Token}, Func, Val};
+use itertools::Itertools;
+
+use crate::{eval::{Token}, lexer::Sentences, ALError, Func, Scope, Val};
 
 
 #[derive(Debug, Clone, PartialEq)]
@@ -18,6 +20,7 @@ pub enum Verb {
     Comp { u: Box<Verb>, v: Box<Verb>},
     Fork { f: Box<Verb>, g: Box<Verb>, h: Box<Verb>},
     Id(Box<Val>),
+    Expl { def: Box<Explicit>, u: Option<Box<Verb>>, v: Option<Box<Verb>> },
 }
 
 impl Display for Verb {
@@ -30,11 +33,109 @@ impl Display for Verb {
             Comp { u, v } => f.write_fmt(format_args!("({u} {v})")),
             Fork { f:ff, g, h } => f.write_fmt(format_args!("({ff} {g} {h})")),
             Id(v) => f.write_str(&format!("{v}").blue()),
+            Expl { def, u, v } => {
+                if let Some(u) = u {
+                    f.write_fmt(format_args!("{u}"))?;
+                }
+                f.write_fmt(format_args!("({def})"))?;
+                if let Some(v) = v {
+                    f.write_fmt(format_args!("{v}"))?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+impl Display for Adverb {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Adverb::*;
+        match self {
+            Prim(p) => f.write_str(&format!("{p}").cyan().to_string()),
+            Train(t) => f.write_fmt(format_args!("({} {} {})", t.0, t.1, t.2)),
+            Conj { left: true, u, src } => f.write_fmt(format_args!("({u}{src})")),
+            Conj { left: false, u, src } => f.write_fmt(format_args!("({src}{u})")),
+            Expl(def) => f.write_fmt(format_args!("({def})")),
+        }
+    }
+}
+
+impl Display for Conj {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Conj::*;
+        match self {
+            Prim(p) => f.write_str(&format!("{p}").magenta().to_string()),
+            Train(t) => f.write_fmt(format_args!("({} {} {})", t.0, t.1, t.2)),
+            Expl(def) => f.write_fmt(format_args!("({def})")),
+        }
+    }
+}
+
+/// Stands for an infinite rank, `_` in a rank noun.
+pub const RANK_INF: i64 = i64::MAX;
+
+impl PrimVerb {
+    /// Monadic, left and right ranks.
+    pub fn ranks(&self) -> [i64; 3] {
+        use PrimVerb::*;
+        const I: i64 = RANK_INF;
+        match self {
+            plus | plus_dot | star | star_dot | dash | dash_dot | pcnt | pcnt_col | larr_dot | larr_col
+                | rarr_dot | rarr_col | hat | hat_dot | bar | o_dot => [0, 0, 0],
+            equal | larr | rarr => [I, 0, 0],
+            i_dot | excl => [1, I, I],
+            i_col => [0, I, I],
+            hash | dllr | lcrl_dot | rcrl_dot => [I, 1, I],
+            lcrl | semi_col | u_dot | u_col => [1, I, I],
+            hash_col | h_dot | h_col | H_dot | H_col | lcrl_col | rcrl | rcrl_col
+                | lbrak | rbrak | semi | semi_dot | comma | slsh_col | bslsh_col | e_dot | E_dot
+                | tlde_dot | tlde_col | hash_dot | s_dot | s_col => [I, I, I],
         }
     }
 }
 
+/// Reads the ranks of `u"n` from `n`: one rank for all three, `l r` as the dyadic pair
+/// with `r` also monadic, or all three spelled out.
+pub fn ranks_of(n: &Val) -> Result<[i64; 3], ALError> {
+    let rank = |f: f64| match f {
+        f64::INFINITY => Ok(RANK_INF),
+        f64::NEG_INFINITY => Ok(-RANK_INF),
+        f if f.fract() == 0.0 => Ok(f as i64),
+        f => ALError::as_Domain(format!("rank must be an integer, got {f}")),
+    };
+    let r = match n {
+        Val::Int(r) => vec![*r],
+        Val::Float(f) => vec![rank(*f)?],
+        Val::IntArr(a) if a.rank() == 1 => a.data.to_vec(),
+        Val::FloatArr(a) if a.rank() == 1 => a.data.iter().map(|f| rank(*f)).collect::<Result<_, _>>()?,
+        n => return ALError::as_Domain(format!("rank must be numeric, got {n}")),
+    };
+    match r[..] {
+        [r] => Ok([r, r, r]),
+        [l, r] => Ok([r, l, r]),
+        [m, l, r] => Ok([m, l, r]),
+        _ => ALError::as_Length(format!("rank takes 1 to 3 numbers, got {}", r.len())),
+    }
+}
+
+/// Ranks of `u"v`, given by the noun `v` or taken over from the verb `v`.
+pub fn rank_operand(v: &Verb) -> Result<[i64; 3], ALError> {
+    match v {
+        Verb::Id(n) => ranks_of(n),
+        v => Ok(v.ranks()),
+    }
+}
+
 impl Verb {
+    /// Monadic, left and right ranks, infinite for anything that takes its arguments whole.
+    pub fn ranks(&self) -> [i64; 3] {
+        match self {
+            Verb::Prim(p) => p.ranks(),
+            Verb::Conj { p: PrimConj::quot, v, .. } => rank_operand(v).unwrap_or([RANK_INF; 3]),
+            _ => [RANK_INF; 3],
+        }
+    }
+
     pub fn identity(&self, y: Val) -> Option<Val> {
         use PrimVerb::*;
         if let Verb::Prim(v) = self {
@@ -58,6 +159,45 @@ impl Verb {
     }
 }
 
+/// An explicit definition `m : 'body'`, split into its monadic and dyadic sentences.
+/// Verbs (`3`) are monadic unless a lone `:` line separates a dyadic part, `4` is dyadic only,
+/// and modifiers (`1`, `2`) without a separator use the whole body for both valences.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explicit {
+    pub m: i64,
+    pub src: String,
+    pub mon: Vec<String>,
+    pub dyd: Vec<String>,
+    /// The local names where the definition was made, `None` at the top level.
+    pub scope: Option<Scope>,
+}
+
+impl Explicit {
+    pub fn new(m: i64, src: String, scope: Option<Scope>) -> Self {
+        let mut sentences = Sentences::default();
+        let body = src.lines()
+            .filter_map(|l| sentences.push(l))
+            .filter(|s| !s.trim().is_empty())
+            .collect_vec();
+        let (mon, dyd) = match body.iter().position(|s| s.trim() == ":") {
+            Some(i) => (body[..i].to_vec(), body[i + 1..].to_vec()),
+            None => match m {
+                1 | 2 => (body.clone(), body),
+                4 => (Vec::new(), body),
+                _ => (body, Vec::new()),
+            },
+        };
+        Explicit { m, src, mon, dyd, scope }
+    }
+}
+
+impl Display for Explicit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let src = self.src.replace('\\', "\\\\").replace('\n', "\\n").replace('\'', "''");
+        f.write_fmt(format_args!("{} : '{}'", self.m, src))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Adverb {
     Prim(PrimAdv),
@@ -67,32 +207,36 @@ pub enum Adverb {
         u: Box<Verb>,
         src: Box<Conj>,
     },
+    Expl(Box<Explicit>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Conj {
     Prim(PrimConj),
     Train(Box<(Func, Func, Func)>),
+    Expl(Box<Explicit>),
 }
 
 impl Adverb {
-    pub fn cons(self, v: Verb) -> Verb {
-        match self {
+    pub fn cons(self, v: Verb) -> Result<Verb, ALError> {
+        Ok(match self {
             Self::Prim(p) => Verb::Adv { u: Box::new(v), p },
             Self::Train(t) => {
                 use Func::*;
                 match *t {
-                    (A(f), V(g), V(h)) => crate::Verb::fork(f.cons(v), g, h),
-                    (A(f), A(g), A(h)) => h.cons(g.cons(f.cons(v))),
+                    (A(f), V(g), V(h)) => crate::Verb::fork(f.cons(v)?, g, h),
+                    (A(f), A(g), A(h)) => h.cons(g.cons(f.cons(v)?)?)?,
                     //(N(f), C(g), A(h)) => g.cons2(crate::Verb::Id(Box::new(f)), h.cons(v)),
-                    (V(f), C(g), A(h)) => g.cons2(f, h.cons(v)),
+                    (V(f), C(g), A(h)) => g.cons2(f, h.cons(v)?)?,
                     //(A(f), C(g), N(h)) => g.cons2(f.cons(v), crate::Verb::Id(Box::new(f))),
-                    (A(f), C(g), V(h)) => g.cons2(f.cons(v), h),
-                    (f, g, h) => panic!("unexpected train: {f:?} {g:?} {h:?}"),
+                    (A(f), C(g), V(h)) => g.cons2(f.cons(v)?, h)?,
+                    (f, g, h) => return ALError::as_Nyi(format!("adverb train: {f:?} {g:?} {h:?}")),
                 }
             }
-            _ => panic!("nyi: {self:?}"),
-        }
+            Self::Conj { left: true, u, src } => src.cons2(*u, v)?,
+            Self::Conj { left: false, u, src } => src.cons2(v, *u)?,
+            Self::Expl(def) => Verb::Expl { def, u: Some(Box::new(v)), v: None },
+        })
     }
 }
 
@@ -101,30 +245,35 @@ impl Conj {
         Adverb::Conj { left, u: Box::new(u), src: Box::new(self)}
     }
 
-    pub fn cons2(self, u: Verb, v: Verb) -> Verb {
-        match self {
+    pub fn cons2(self, u: Verb, v: Verb) -> Result<Verb, ALError> {
+        Ok(match self {
+            Self::Prim(PrimConj::quot) => {
+                rank_operand(&v)?;
+                Verb::Conj { u: Box::new(u), p: PrimConj::quot, v: Box::new(v)}
+            },
             Self::Prim(p) => Verb::Conj { u: Box::new(u), p, v: Box::new(v)},
             Self::Train(t) => {
                 use Func::*;
                 match *t {
-                    (V(f), V(g), C(h)) => crate::Verb::fork(f, g, h.cons2(u, v)),
+                    (V(f), V(g), C(h)) => crate::Verb::fork(f, g, h.cons2(u, v)?),
                     //(N(f), V(g), C(h)) => 
-                    (C(f), V(g), V(h)) => crate::Verb::fork(f.cons2(u, v), g, h),
-                    (C(f), V(g), C(h)) => crate::Verb::fork(f.cons2(u.clone(), v.clone()), g, h.cons2(u, v)),
-                    (A(f), A(g), V(h)) => crate::Verb::fork(f.cons(u), g.cons(v), h),
-                    (C(f), A(g), A(h)) => h.cons(g.cons(f.cons2(u, v))),
+                    (C(f), V(g), V(h)) => crate::Verb::fork(f.cons2(u, v)?, g, h),
+                    (C(f), V(g), C(h)) => crate::Verb::fork(f.cons2(u.clone(), v.clone())?, g, h.cons2(u, v)?),
+                    (A(f), A(g), V(h)) => crate::Verb::fork(f.cons(u)?, g.cons(v)?, h),
+                    (C(f), A(g), A(h)) => h.cons(g.cons(f.cons2(u, v)?)?)?,
                     //(N(f), C(g), C(h)) => 
-                    (V(v1), C(c1), C(c2)) => c1.cons2(v1, c2.cons2(u, v)),
-                    (A(f), C(g), A(h)) => g.cons2(f.cons(u), h.cons(v)),
-                    (A(f), C(g), C(h)) => g.cons2(f.cons(u.clone()), h.cons2(u, v)),
+                    (V(v1), C(c1), C(c2)) => c1.cons2(v1, c2.cons2(u, v)?)?,
+                    (A(f), C(g), A(h)) => g.cons2(f.cons(u)?, h.cons(v)?)?,
+                    (A(f), C(g), C(h)) => g.cons2(f.cons(u.clone())?, h.cons2(u, v)?)?,
                     //(C(f), C(g), N(h)) =>
-                    (C(f), C(g), V(h)) => g.cons2(f.cons2(u,v), h),
-                    (C(f), C(g), A(h)) => g.cons2(f.cons2(u, v.clone()), h.cons(v)),
-                    (C(f), C(g), C(h)) => g.cons2(f.cons2(u.clone(), v.clone()), h.cons2(u, v)),
-                    _ => panic!("nyi"),
+                    (C(f), C(g), V(h)) => g.cons2(f.cons2(u,v)?, h)?,
+                    (C(f), C(g), A(h)) => g.cons2(f.cons2(u, v.clone())?, h.cons(v)?)?,
+                    (C(f), C(g), C(h)) => g.cons2(f.cons2(u.clone(), v.clone())?, h.cons2(u, v)?)?,
+                    (f, g, h) => return ALError::as_Nyi(format!("conjunction train: {f:?} {g:?} {h:?}")),
                 }
             }
-        }
+            Self::Expl(def) => Verb::Expl { def, u: Some(Box::new(u)), v: Some(Box::new(v)) },
+        })
     }
 }
 
@@ -150,15 +299,39 @@ pub enum PrimVerb {
     dllr,
     excl,
     plus,
+    plus_dot,
     star,
+    star_dot,
     dash,
+    dash_dot,
     pcnt,
+    pcnt_col,
     larr,
+    larr_dot,
+    larr_col,
     rarr,
+    rarr_dot,
+    rarr_col,
+    hat,
+    hat_dot,
+    bar,
+    o_dot,
     equal,
     semi,
     semi_dot,
     semi_col,
+    comma,
+    slsh_col,
+    bslsh_col,
+    e_dot,
+    E_dot,
+    tlde_dot,
+    tlde_col,
+    hash_dot,
+    u_dot,
+    u_col,
+    s_dot,
+    s_col,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -169,6 +342,8 @@ pub enum PrimAdv {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum PrimConj {
+    col,
+    quot,
     at,
     ampr,
     ampr_dot,
@@ -183,7 +358,8 @@ pub enum Part {
     Conj(PrimConj),
     Lpar,
     Rpar,
-    Asgn,
+    /// `=.` when local, `=:` otherwise
+    Asgn { local: bool },
     //Mark,
     //Noun,
     //Name,
@@ -266,7 +442,7 @@ impl TryFrom<&str> for PseudoChar {
         if w.len() < 3 {
             SPELL_IN_OUT.get().unwrap().spell_in[c as usize][infl as usize].ok_or(())
         } else {
-            unreachable!("{:?}", w);
+            Err(())
         }
         
     }
@@ -288,7 +464,7 @@ impl SpellInOut {
              Verb(v) => _ = self.verb.insert(v, c),
              Adv(a) => _ = self.adv.insert(a, c),
             Conj(cj) => _ = self.conj.insert(cj, c),
-            Null | Rpar | Lpar | Asgn => (),
+            Null | Rpar | Lpar | Asgn { .. } => (),
         };
     }
 
@@ -332,32 +508,43 @@ impl SpellInOut {
         use PrimVerb::*;
         use PrimAdv::*;
         use PrimConj::*;
-        a.setup(b'+', [1, 0, 0], (Verb(plus), Null, Null));
-        a.setup( b'-', [1, 0, 0], (Verb(dash), Null, Null));
-        a.setup( b'*', [1, 0, 0], (Verb(star), Null, Null));
-        a.setup( b'%', [1, 0, 0], (Verb(pcnt), Null, Null));
+        a.setup(b'+', [1, 1, 0], (Verb(plus), Verb(plus_dot), Null));
+        a.setup( b'-', [1, 1, 0], (Verb(dash), Verb(dash_dot), Null));
+        a.setup( b'*', [1, 1, 0], (Verb(star), Verb(star_dot), Null));
+        a.setup( b'%', [1, 0, 1], (Verb(pcnt), Null, Verb(pcnt_col)));
+        a.setup( b'^', [1, 1, 0], (Verb(hat), Verb(hat_dot), Null));
+        a.setup( b'|', [1, 0, 0], (Verb(bar), Null, Null));
         a.setup( b'!', [1, 0, 0], (Verb(excl), Null, Null));
         a.setup( b'$', [1, 0, 0], (Verb(dllr), Null, Null));
         a.setup( b'@', [1, 0, 0], (Conj(at), Null, Null));
-        a.setup( b'#', [1, 0, 1], (Verb(hash), Null, Verb(hash_col)));
-        a.setup( b'<', [1, 0, 0], (Verb(larr), Null, Null));
-        a.setup( b'>', [1, 0, 0], (Verb(rarr), Null, Null));
+        a.setup( b'"', [1, 0, 0], (Conj(quot), Null, Null));
+        a.setup( b':', [1, 0, 0], (Conj(col), Null, Null));
+        a.setup( b'#', [1, 1, 1], (Verb(hash), Verb(hash_dot), Verb(hash_col)));
+        a.setup( b'~', [0, 1, 1], (Null, Verb(tlde_dot), Verb(tlde_col)));
+        a.setup( b'<', [1, 1, 1], (Verb(larr), Verb(larr_dot), Verb(larr_col)));
+        a.setup( b'>', [1, 1, 1], (Verb(rarr), Verb(rarr_dot), Verb(rarr_col)));
         a.setup( b'{', [1, 1, 0], (Verb(lcrl), Verb(lcrl_dot), Verb(lcrl_col)));
         a.setup( b'}', [1, 1, 0], (Verb(rcrl), Verb(rcrl_dot), Verb(rcrl_col)));
+        a.setup( b',', [1, 0, 0], (Verb(comma), Null, Null));
         a.setup( b'[', [1, 0, 0], (Verb(lbrak), Null, Null));
         a.setup( b']', [1, 0, 0], (Verb(rbrak), Null, Null));
         a.setup( b'i', [0, 1, 1], (Null, Verb(i_dot), Verb(i_col)));
         a.setup( b'h', [0, 1, 1], (Null, Verb(h_dot), Verb(h_col)));
         a.setup( b'H', [0, 1, 1], (Null, Verb(H_dot), Verb(H_col)));
+        a.setup( b'o', [0, 1, 0], (Null, Verb(o_dot), Null));
+        a.setup( b'e', [0, 1, 0], (Null, Verb(e_dot), Null));
+        a.setup( b'E', [0, 1, 0], (Null, Verb(E_dot), Null));
+        a.setup( b'u', [0, 1, 1], (Null, Verb(u_dot), Verb(u_col)));
+        a.setup( b's', [0, 1, 1], (Null, Verb(s_dot), Verb(s_col)));
 
 
         a.setup( b'&', [0, 1, 1], (Null, Conj(ampr_dot), Conj(ampr_col)));
 
-        a.setup( b'/', [1, 0, 0], (Adv(slsh), Null, Null));
-        a.setup( b'\\', [1, 0, 0], (Adv(bslsh), Null, Null));
+        a.setup( b'/', [1, 0, 1], (Adv(slsh), Null, Verb(slsh_col)));
+        a.setup( b'\\', [1, 0, 1], (Adv(bslsh), Null, Verb(bslsh_col)));
 
         a.setup( b';', [1, 1, 1], (Verb(semi), Verb(semi_dot), Verb(semi_col)));
-        a.setup( b'=', [1, 0, 1], (Verb(equal), Null, Asgn));
+        a.setup( b'=', [1, 1, 1], (Verb(equal), Asgn { local: true }, Asgn { local: false }));
         a.setup( b'(', [1, 0, 0], (Lpar, Null, Null));
         a.setup( b')', [1, 0, 0], (Rpar, Null, Null));
         a